@@ -0,0 +1,43 @@
+// Plays whole games with the autopilot and no window, e.g. `cargo run --example autopilot -- 12x12 10`
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use snake::autopilot::Autopilot;
+use snake::level::Level;
+use snake::rules::GameRules;
+use snake::settings::parse_board_size;
+use snake::sim::{SimEvent, SnakeSim};
+
+fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    let (width, height) = args.get(1).and_then(|size| parse_board_size(size)).unwrap_or((12, 12));
+    let games = args.get(2).and_then(|games| games.parse().ok()).unwrap_or(10);
+
+    let level = Level::open(width, height);
+    let autopilot = Autopilot::new(&level);
+    for seed in 0..games {
+        let mut sim = SnakeSim::new(level.clone(), GameRules::default(), 1);
+        let mut rng = StdRng::seed_from_u64(seed);
+        sim.reset(&mut rng);
+
+        // any food is reached within a lap of the board, a snake that goes several laps without eating is going in circles
+        let (mut ticks, mut hungry) = (0, 0);
+        while sim.game_over.is_none() && hungry < 4 * level.cell_count() {
+            let direction = autopilot.direction(&sim, 0);
+            let events = sim.step(&[direction], &mut rng);
+            let ate = events.iter().any(|event| matches!(event, SimEvent::FoodEaten { .. }));
+            hungry = if ate { 0 } else { hungry + 1 };
+            ticks += 1;
+        }
+        let Some(reason) = sim.game_over else {
+            println!(
+                "seed {}: stalled, nothing eaten in the last {} of {} ticks, length {}/{}",
+                seed, hungry, ticks, sim.snakes[0].segments.len(), level.cell_count()
+            );
+            continue;
+        };
+        println!(
+            "seed {}: {:?} after {} ticks, length {}/{}",
+            seed, reason, ticks, sim.snakes[0].segments.len(), level.cell_count()
+        );
+    }
+}
@@ -0,0 +1,119 @@
+// Plays a networked match between two computer snakes over localhost without a window, e.g.
+// `cargo run --example lockstep -- 7777`; add `desync` to give the guest different rules and watch it get caught
+use std::collections::VecDeque;
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+use snake::ai::{choose_direction, Difficulty};
+use snake::level::Level;
+use snake::net::lockstep::{self, Connection, Lockstep, Message, NetError, PLAYERS};
+use snake::rng::GameRng;
+use snake::rules::GameRules;
+use snake::sim::{GameOverReason, SnakeSim};
+use snake::snake::Direction;
+
+// two snakes that keep out of each other's way could go on for a long time
+const MAX_TICKS: u64 = 10_000;
+
+fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    let port = args.get(1).and_then(|port| port.parse().ok()).unwrap_or(lockstep::DEFAULT_PORT);
+    let desync = args.iter().any(|arg| arg == "desync");
+
+    let listener = lockstep::listen(port).expect("port is free");
+    let guest = thread::spawn(move || {
+        let stream = TcpStream::connect(("127.0.0.1", port)).expect("host is listening");
+        let mut connection = Connection::new(stream).expect("socket accepts options");
+        connection.send(&Message::hello());
+        let mut messages = next_messages(&mut connection).into_iter();
+        let Some(Message::Start { seed, level, mut rules, player }) = messages.next() else {
+            panic!("host starts the game");
+        };
+        if desync {
+            rules.start.length += 1;
+        }
+        // the host's first inputs may have come along with the round
+        let mut lockstep = Lockstep::new(connection, player, seed, level, rules);
+        for message in messages {
+            lockstep.handle(message).expect("host sends inputs");
+        }
+        play(lockstep)
+    });
+
+    let stream = loop {
+        match listener.accept() {
+            Ok((stream, _)) => break stream,
+            Err(_) => thread::sleep(Duration::from_millis(1)),
+        }
+    };
+    let mut connection = Connection::new(stream).expect("socket accepts options");
+    assert_eq!(next_messages(&mut connection), vec![Message::hello()]);
+    let (level, rules, seed) = (Level::open(16, 12), GameRules::default(), rand::random());
+    connection.send(&Message::Start { seed, level: level.clone(), rules: rules.clone(), player: 1 });
+    let host = play(Lockstep::new(connection, 0, seed, level, rules));
+
+    println!("host:  {}", host);
+    println!("guest: {}", guest.join().unwrap());
+}
+
+fn next_messages(connection: &mut Connection) -> Vec<Message> {
+    loop {
+        let messages = connection.poll().expect("peer stays connected");
+        if !messages.is_empty() {
+            return messages;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+fn play(mut lockstep: Lockstep) -> String {
+    let local = lockstep.local;
+    let mut sim = SnakeSim::new(lockstep.level.clone(), lockstep.rules.clone(), PLAYERS);
+    sim.reset(&mut lockstep.rng);
+    // the local inputs sent but not played yet, the next one is decided from where they take the snake
+    let mut pending = VecDeque::new();
+    let mut ticks = 0;
+    while sim.game_over.is_none() {
+        if ticks == MAX_TICKS {
+            return format!("no result after {} ticks, state hash {:016x}", ticks, sim.state_hash());
+        }
+        let rng = lockstep.rng.clone();
+        let next_input = |_| {
+            let direction = plan(&sim, &rng, local, &pending);
+            pending.push_back(direction);
+            direction
+        };
+        match lockstep.exchange(sim.state_hash(), next_input) {
+            Ok(Some(inputs)) => {
+                sim.step(&inputs, &mut lockstep.rng);
+                pending.pop_front();
+                ticks += 1;
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(1)),
+            Err(err @ NetError::Desync { .. }) => return format!("stopped, {}", err),
+            Err(err) => return format!("failed after {} ticks: {}", ticks, err),
+        }
+    }
+    let outcome = match sim.game_over.unwrap() {
+        GameOverReason::Winner(0) => "the host won".to_string(),
+        GameOverReason::Winner(_) => "the guest won".to_string(),
+        GameOverReason::Draw => "a draw".to_string(),
+        reason => format!("{:?}", reason),
+    };
+    format!("{} after {} ticks, state hash {:016x}", outcome, ticks, sim.state_hash())
+}
+
+// plays a copy of the game through the inputs still on their way, guessing the other snake plays like this one
+fn plan(sim: &SnakeSim, rng: &GameRng, local: usize, pending: &VecDeque<Direction>) -> Direction {
+    let (mut ahead, mut rng) = (sim.clone(), rng.clone());
+    for &direction in pending {
+        if ahead.game_over.is_some() {
+            break;
+        }
+        let inputs = ahead.snakes.iter().enumerate()
+            .map(|(player, _)| if player == local { direction } else { choose_direction(&ahead, player, Difficulty::Normal) })
+            .collect::<Vec<_>>();
+        ahead.step(&inputs, &mut rng);
+    }
+    choose_direction(&ahead, local, Difficulty::Normal)
+}
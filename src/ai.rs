@@ -0,0 +1,172 @@
+use std::collections::{HashSet, VecDeque};
+use bevy::prelude::*;
+
+use crate::board::Position;
+use crate::controls::{DirectionQueue, Player};
+use crate::sim::SnakeSim;
+use crate::snake::Direction;
+use crate::game_is_running;
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, steer_ai_snakes
+            .before(crate::tick)
+            .run_if(game_is_running));
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Difficulty {
+    // heads for the closest food, only avoiding moves that crash right away
+    Easy,
+    // like easy, but skips moves that lead into a pocket smaller than itself
+    #[default]
+    Normal,
+    // follows the shortest path to food when there's room left at the end of it
+    Hard,
+}
+
+impl Difficulty {
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+}
+
+impl std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "Easy"),
+            Difficulty::Normal => write!(f, "Normal"),
+            Difficulty::Hard => write!(f, "Hard"),
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct AiController {
+    pub difficulty: Difficulty,
+}
+
+fn steer_ai_snakes(
+    sim: Res<SnakeSim>,
+    mut players: Query<(&Player, &AiController, &mut DirectionQueue)>,
+) {
+    for (player, ai, mut queue) in players.iter_mut() {
+        let Some(snake) = sim.snakes.get(player.index).filter(|snake| !snake.is_out()) else {
+            continue;
+        };
+        queue.clear();
+        queue.push(choose_direction(&sim, player.index, ai.difficulty), snake.heading);
+    }
+}
+
+pub fn choose_direction(sim: &SnakeSim, player: usize, difficulty: Difficulty) -> Direction {
+    let snake = &sim.snakes[player];
+    let blocked = blocked_cells(sim);
+    let moves = safe_moves(sim, player, &blocked);
+    if moves.is_empty() {
+        return snake.heading;
+    }
+
+    let roomy = moves.iter()
+        .copied()
+        .filter(|(_, next)| reachable_cells(sim, *next, &blocked, snake.segments.len()) >= snake.segments.len())
+        .collect::<Vec<_>>();
+    match difficulty {
+        Difficulty::Easy => closest_to_food(sim, &moves),
+        Difficulty::Normal if roomy.is_empty() => most_room(sim, &moves, &blocked),
+        Difficulty::Normal => closest_to_food(sim, &roomy),
+        Difficulty::Hard => path_to_food(sim, player, &blocked)
+            .filter(|direction| roomy.iter().any(|(roomy, _)| roomy == direction))
+            .unwrap_or_else(|| most_room(sim, &moves, &blocked)),
+    }
+}
+
+fn blocked_cells(sim: &SnakeSim) -> HashSet<Position> {
+    sim.level.walls.iter()
+        .chain(sim.snakes.iter().flat_map(|snake| snake.segments.iter()))
+        .copied()
+        .collect()
+}
+
+// moves that don't crash this tick, also keeping clear of cells another head can reach
+fn safe_moves(sim: &SnakeSim, player: usize, blocked: &HashSet<Position>) -> Vec<(Direction, Position)> {
+    let snake = &sim.snakes[player];
+    let contested = sim.snakes.iter()
+        .enumerate()
+        .filter(|(other, _)| *other != player)
+        .flat_map(|(_, other)| other.segments.front())
+        .flat_map(|head| Direction::ALL.iter().filter_map(|direction| sim.next_position(*head, *direction)))
+        .collect::<HashSet<_>>();
+    let moves = Direction::ALL.iter()
+        .filter(|direction| **direction != snake.heading.opposite())
+        .filter_map(|direction| sim.next_position(snake.segments[0], *direction).map(|next| (*direction, next)))
+        .filter(|(_, next)| !blocked.contains(next))
+        .collect::<Vec<_>>();
+    let uncontested = moves.iter()
+        .copied()
+        .filter(|(_, next)| !contested.contains(next))
+        .collect::<Vec<_>>();
+    if uncontested.is_empty() { moves } else { uncontested }
+}
+
+fn reachable_cells(sim: &SnakeSim, start: Position, blocked: &HashSet<Position>, limit: usize) -> usize {
+    let mut seen = HashSet::from([start]);
+    let mut frontier = VecDeque::from([start]);
+    while let Some(cell) = frontier.pop_front() {
+        if seen.len() >= limit {
+            break;
+        }
+        for next in Direction::ALL.iter().filter_map(|direction| sim.next_position(cell, *direction)) {
+            if !blocked.contains(&next) && seen.insert(next) {
+                frontier.push_back(next);
+            }
+        }
+    }
+    seen.len()
+}
+
+fn closest_to_food(sim: &SnakeSim, moves: &[(Direction, Position)]) -> Direction {
+    let distance = |from: Position| sim.food.iter()
+        .map(|food| food.position.x.abs_diff(from.x) as u32 + food.position.y.abs_diff(from.y) as u32)
+        .min()
+        .unwrap_or_default();
+    moves.iter()
+        .min_by_key(|(_, next)| distance(*next))
+        .map(|(direction, _)| *direction)
+        .unwrap_or_default()
+}
+
+fn most_room(sim: &SnakeSim, moves: &[(Direction, Position)], blocked: &HashSet<Position>) -> Direction {
+    moves.iter()
+        .max_by_key(|(_, next)| reachable_cells(sim, *next, blocked, sim.level.cell_count()))
+        .map(|(direction, _)| *direction)
+        .unwrap_or_default()
+}
+
+// first step of the shortest path to any food, walking around everything that blocks now
+fn path_to_food(sim: &SnakeSim, player: usize, blocked: &HashSet<Position>) -> Option<Direction> {
+    let head = sim.snakes[player].segments[0];
+    let mut seen = HashSet::from([head]);
+    let mut frontier = Direction::ALL.iter()
+        .filter_map(|direction| sim.next_position(head, *direction).map(|next| (*direction, next)))
+        .filter(|(_, next)| !blocked.contains(next) && seen.insert(*next))
+        .collect::<VecDeque<_>>();
+    while let Some((first, cell)) = frontier.pop_front() {
+        if sim.food.iter().any(|food| food.position == cell) {
+            return Some(first);
+        }
+        for next in Direction::ALL.iter().filter_map(|direction| sim.next_position(cell, *direction)) {
+            if !blocked.contains(&next) && seen.insert(next) {
+                frontier.push_back((first, next));
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,27 @@
+use crate::settings::{parse_board_size, Settings};
+
+// the command line options the game and the server share
+
+pub fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
+
+// `--port 7777`, the game connects or listens on it and the server listens on it
+pub fn port_from_args() -> Option<u16> {
+    arg_value("--port").and_then(|port| port.parse().ok())
+}
+
+// `--edges wrap`, `--board 24x16` and `--level box.level` pick the board, `--autopilot` starts with player one on
+// autopilot
+pub fn settings_from_args() -> Settings {
+    let mut settings = Settings::default();
+    if let Some(edge_policy) = arg_value("--edges").and_then(|edges| edges.parse().ok()) {
+        settings.edge_policy = Some(edge_policy);
+    }
+    if let Some((width, height)) = arg_value("--board").as_deref().and_then(parse_board_size) {
+        (settings.board_width, settings.board_height) = (width, height);
+    }
+    settings.level = arg_value("--level");
+    settings.autopilot = std::env::args().any(|arg| arg == "--autopilot");
+    settings
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+use bevy::prelude::*;
+
+use crate::autopilot::Autopilot;
+use crate::board::BoardView;
+use crate::controls::Player;
+use crate::level::{Level, Levels};
+use crate::rng::GameRng;
+use crate::rules::GameRules;
+use crate::settings::Settings;
+use crate::sim::SnakeSim;
+use crate::{start_round, GameState};
+
+const ATTRACT_DELAY: Duration = Duration::from_secs(5);
+
+pub struct AttractPlugin;
+
+impl Plugin for AttractPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Idle>()
+            .add_systems(Update, track_idle.run_if(in_state(GameState::Menu)))
+            .add_systems(Update, stop_demo.run_if(resource_changed::<Settings>().and_then(in_state(GameState::Demo))))
+            .add_systems(OnEnter(GameState::Demo), start_demo)
+            .add_systems(OnEnter(GameState::Starting), reset_idle);
+    }
+}
+
+// how long the menu has been left alone, kept between demos so a finished one is followed by the next right away
+#[derive(Resource, Default)]
+struct Idle(Duration);
+
+fn track_idle(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    mut cursor: EventReader<CursorMoved>,
+    mut idle: ResMut<Idle>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let active = cursor.iter().count() > 0
+        || keys.get_pressed().next().is_some()
+        || buttons.get_pressed().next().is_some();
+    idle.0 = if active { Duration::ZERO } else { idle.0 + time.delta() };
+    if idle.0 >= ATTRACT_DELAY {
+        next_state.set(GameState::Demo);
+    }
+}
+
+fn reset_idle(mut idle: ResMut<Idle>) {
+    idle.0 = Duration::ZERO;
+}
+
+// the demo doesn't touch the score, only the board, the sim and the rng that a new game resets anyway
+fn start_demo(
+    mut commands: Commands,
+    mut board: BoardView,
+    players: Query<Entity, With<Player>>,
+    mut fixed_time: ResMut<FixedTime>,
+    mut rng: ResMut<GameRng>,
+    mut sim: ResMut<SnakeSim>,
+    settings: Res<Settings>,
+    levels: Res<Levels>,
+    level_assets: Res<Assets<Level>>,
+    rules: Res<GameRules>,
+) {
+    let round = SnakeSim::new(levels.current(&settings, &level_assets), rules.with_settings(&settings), 1);
+    for entity in start_round(&mut commands, &mut board, &players, &mut fixed_time, &mut rng, &mut sim, round) {
+        commands.entity(entity).insert(Autopilot::new(&sim.level));
+    }
+}
+
+// a changed board needs a new demo, going back to the idle menu starts one straight away; "New Game" and "Versus"
+// change the settings too, and the game they start wins over the menu whichever system runs first
+fn stop_demo(mut next_state: ResMut<NextState<GameState>>) {
+    if next_state.0.is_none() {
+        next_state.set(GameState::Menu);
+    }
+}
@@ -0,0 +1,144 @@
+use bevy::prelude::*;
+
+use crate::ai::{self, Difficulty};
+use crate::board::Position;
+use crate::controls::{DirectionQueue, KeyBindings, Player};
+use crate::level::Level;
+use crate::net::lockstep::Lockstep;
+use crate::sim::SnakeSim;
+use crate::snake::Direction;
+use crate::{game_is_running, GameState};
+
+// cells kept free between the head and the tail when taking a shortcut, to leave room for growing
+const SHORTCUT_MARGIN: usize = 4;
+
+pub struct AutopilotPlugin;
+
+impl Plugin for AutopilotPlugin {
+    fn build(&self, app: &mut App) {
+        // a networked game plays inputs a few ticks after they are sent, the autopilot would steer from a stale board
+        app.add_systems(Update, toggle_autopilot
+                .run_if(in_state(GameState::Playing).and_then(not(resource_exists::<Lockstep>()))))
+            .add_systems(FixedUpdate, steer_autopilot
+                .before(crate::tick)
+                .run_if(game_is_running));
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HamiltonianCycle {
+    width: u8,
+    // place of every cell along the cycle, indexed by y * width + x
+    order: Vec<usize>,
+}
+
+impl HamiltonianCycle {
+    // only open boards with an even side have a cycle through every cell
+    pub fn new(level: &Level) -> Option<Self> {
+        let (width, height) = (usize::from(level.width), usize::from(level.height));
+        if !level.walls.is_empty() || width < 2 || height < 2 || width % 2 == 1 && height % 2 == 1 {
+            return None;
+        }
+
+        // along the first row, serpentine back through the rest leaving the first column free, down that column
+        let transposed = height % 2 == 1;
+        let (columns, rows) = if transposed { (height, width) } else { (width, height) };
+        let path = (0..columns).map(|column| (column, 0))
+            .chain((1..rows).flat_map(|row| {
+                let columns = (1..columns).map(move |column| (column, row));
+                if row % 2 == 1 { columns.rev().collect::<Vec<_>>() } else { columns.collect() }
+            }))
+            .chain((1..rows).rev().map(|row| (0, row)));
+
+        let mut order = vec![0; width * height];
+        for (index, (column, row)) in path.enumerate() {
+            let (x, y) = if transposed { (row, column) } else { (column, row) };
+            order[y * width + x] = index;
+        }
+        Some(Self { width: level.width, order })
+    }
+
+    fn index(&self, position: Position) -> usize {
+        self.order[usize::from(position.y) * usize::from(self.width) + usize::from(position.x)]
+    }
+
+    fn distance(&self, from: Position, to: Position) -> usize {
+        (self.index(to) + self.order.len() - self.index(from)) % self.order.len()
+    }
+
+    // follows the cycle, cutting ahead towards food while the snake is short enough to never cut itself off
+    pub fn direction(&self, sim: &SnakeSim, player: usize) -> Option<Direction> {
+        let snake = &sim.snakes[player];
+        let head = snake.segments[0];
+        let cells = self.order.len();
+        let to_tail = match self.distance(head, *snake.segments.back()?) {
+            0 => cells,
+            distance => distance,
+        };
+        let to_food = sim.food.iter()
+            .map(|food| self.distance(head, food.position))
+            .min()
+            .unwrap_or(1);
+        let shortcuts = snake.segments.len() * 2 < cells;
+        let room = to_tail.saturating_sub(snake.pending_growth as usize + SHORTCUT_MARGIN);
+
+        Direction::ALL.iter()
+            .filter(|direction| **direction != snake.heading.opposite())
+            .filter_map(|direction| sim.next_position(head, *direction).map(|next| (*direction, next)))
+            .filter(|(_, next)| !sim.is_snake(next))
+            .map(|(direction, next)| (direction, self.distance(head, next)))
+            .filter(|(_, step)| *step == 1 || shortcuts && *step <= to_food && *step < room)
+            .max_by_key(|(_, step)| *step)
+            .map(|(direction, _)| direction)
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Autopilot {
+    cycle: Option<HamiltonianCycle>,
+}
+
+impl Autopilot {
+    pub fn new(level: &Level) -> Self {
+        Self { cycle: HamiltonianCycle::new(level) }
+    }
+
+    // boards without a cycle, or a snake that left it, are left to the best computer opponent
+    pub fn direction(&self, sim: &SnakeSim, player: usize) -> Direction {
+        self.cycle.as_ref()
+            .and_then(|cycle| cycle.direction(sim, player))
+            .unwrap_or_else(|| ai::choose_direction(sim, player, Difficulty::Hard))
+    }
+}
+
+// only the first keyboard player's snake, in versus the other player keeps theirs
+fn toggle_autopilot(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    sim: Res<SnakeSim>,
+    players: Query<(Entity, &Player, Option<&Autopilot>), With<KeyBindings>>,
+) {
+    if !keys.just_pressed(KeyCode::Tab) {
+        return;
+    }
+    let Some((entity, _, autopilot)) = players.iter().min_by_key(|(_, player, _)| player.index) else {
+        return;
+    };
+    match autopilot {
+        Some(_) => commands.entity(entity).remove::<Autopilot>(),
+        None => commands.entity(entity).insert(Autopilot::new(&sim.level)),
+    };
+}
+
+fn steer_autopilot(
+    sim: Res<SnakeSim>,
+    mut players: Query<(&Player, &Autopilot, &mut DirectionQueue)>,
+) {
+    for (player, autopilot, mut queue) in players.iter_mut() {
+        let Some(snake) = sim.snakes.get(player.index) else {
+            continue;
+        };
+        queue.clear();
+        queue.push(autopilot.direction(&sim, player.index), snake.heading);
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::Duration;
+use bevy::app::{AppExit, ScheduleRunnerPlugin};
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use snake::args::{port_from_args, settings_from_args};
+use snake::level::Level;
+use snake::net::lockstep::DEFAULT_PORT;
+use snake::net::server::{ServerConfig, ServerPlugin};
+use snake::rules::GameRules;
+
+// plays networked games without a window, e.g. `cargo run --bin server -- --port 7777 --board 24x16`;
+// players connect with "Join Game" and are paired up in the order they arrive
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_millis(1))))
+        .add_plugins(LogPlugin::default());
+    // read once the log is up, a bad argument is reported and quits like a port that can't be listened on
+    match config_from_args() {
+        Ok(config) => app.insert_resource(config).add_plugins(ServerPlugin),
+        Err(err) => app.add_systems(Startup, move |mut exit: EventWriter<AppExit>| {
+            error!("{}", err);
+            exit.send(AppExit);
+        }),
+    };
+    app.run();
+}
+
+// the same rules file and level files the game reads from its assets folder
+fn config_from_args() -> Result<ServerConfig, String> {
+    let settings = settings_from_args();
+    let rules = std::fs::read_to_string("assets/rules.ron").ok()
+        .and_then(|source| ron::de::from_str::<GameRules>(&source).map_err(|err| warn!("rules.ron: {}", err)).ok())
+        .filter(|rules| rules.check().map_err(|err| warn!("rules.ron: {}", err)).is_ok())
+        .unwrap_or_default();
+    let level = match settings.level.as_ref() {
+        Some(path) => std::fs::read_to_string(format!("assets/{}", path))
+            .map_err(|err| err.to_string())
+            .and_then(|source| Level::parse(&source).map_err(|err| err.to_string()))
+            .map_err(|err| format!("can't load level {}: {}", path, err))?,
+        None => Level::open(settings.board_width, settings.board_height),
+    };
+
+    Ok(ServerConfig {
+        port: port_from_args().unwrap_or(DEFAULT_PORT),
+        level,
+        rules: rules.with_settings(&settings),
+    })
+}
@@ -1,14 +1,20 @@
-use bevy::ecs::system::Command;
+use std::collections::HashSet;
+use bevy::ecs::system::{Command, SystemParam};
 use bevy::prelude::*;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 use crate::colors::COLORS;
-use crate::food::{Food, FoodType};
+use crate::food::{Food, FoodLifetime, FoodType};
+use crate::level::{Level, Levels};
+use crate::rules::GameRules;
+use crate::settings::Settings;
+use crate::sim::{SimEvent, SnakeSim};
 
 const TILE_SIZE: f32 = 30.0;
 const TILE_SPACER: f32 = 1.0;
 
-#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash, Component)]
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash, Component, Deserialize, Serialize)]
 pub struct Position {
     pub x: u8,
     pub y: u8,
@@ -16,88 +22,157 @@ pub struct Position {
 
 #[derive(Component)]
 pub struct Board {
-    pub size: u8,
-    physical_size: f32,
+    pub width: u8,
+    pub height: u8,
+    pub walls: HashSet<Position>,
+    physical_width: f32,
+    physical_height: f32,
 }
 
 impl Board {
-    fn new(size: u8) -> Self {
-        let physical_size = f32::from(size) * TILE_SIZE + f32::from(size + 1) * TILE_SPACER;
-
+    fn new(level: &Level) -> Self {
         Board {
-            size,
-            physical_size,
+            width: level.width,
+            height: level.height,
+            walls: level.walls.clone(),
+            physical_width: physical_length(level.width),
+            physical_height: physical_length(level.height),
         }
     }
 
-    fn cell_position_to_physical(&self, pos: u8) -> f32 {
-        let offset = -self.physical_size / 2.0 + 0.5 * TILE_SIZE;
+    fn matches(&self, level: &Level) -> bool {
+        self.width == level.width && self.height == level.height && self.walls == level.walls
+    }
+
+    fn cell_position_to_physical(&self, pos: Position) -> Vec2 {
+        let offset = Vec2::new(-self.physical_width, -self.physical_height) / 2.0 + 0.5 * TILE_SIZE;
 
-        offset + f32::from(pos) * TILE_SIZE
-               + f32::from(pos + 1) * TILE_SPACER
+        offset + Vec2::new(f32::from(pos.x), f32::from(pos.y)) * TILE_SIZE
+               + (Vec2::new(f32::from(pos.x), f32::from(pos.y)) + 1.0) * TILE_SPACER
     }
 }
 
+fn physical_length(cells: u8) -> f32 {
+    f32::from(cells) * TILE_SIZE + (f32::from(cells) + 1.0) * TILE_SPACER
+}
+
 pub fn spawn_board(
     mut commands: Commands,
-    // snake: Res<Snake>,
-    // mut food_event: EventWriter<NewFoodEvent>
+    settings: Res<Settings>,
+    levels: Res<Levels>,
+    level_assets: Res<Assets<Level>>,
 ) {
-    let board = Board::new(20);
+    spawn_board_entity(&mut commands, Board::new(&levels.current(&settings, &level_assets)));
+}
 
+pub fn respawn_board(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    levels: Res<Levels>,
+    level_assets: Res<Assets<Level>>,
+    query_board: Query<(Entity, &Board)>,
+    positions: Query<Entity, With<Position>>,
+) {
+    let level = levels.current(&settings, &level_assets);
+    let (entity, board) = query_board.single();
+    if board.matches(&level) {
+        return;
+    }
+
+    commands.entity(entity).despawn_recursive();
+    for entity in positions.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_board_entity(&mut commands, Board::new(&level));
+}
+
+fn spawn_board_entity(commands: &mut Commands, board: Board) {
     commands.spawn(
         SpriteBundle {
             sprite: Sprite {
                 color: COLORS.board,
-                custom_size: Some(Vec2::new(board.physical_size, board.physical_size)),
+                custom_size: Some(Vec2::new(board.physical_width, board.physical_height)),
                 ..default()
             },
             ..default()
         })
         .with_children(|builder| {
-            for (x, y) in (0..board.size).cartesian_product(0..board.size) {
+            for (x, y) in (0..board.width).cartesian_product(0..board.height) {
+                let color = if board.walls.contains(&Position { x, y }) { COLORS.wall }
+                            else if (x + y) % 2 == 0 { COLORS.tile_placeholder }
+                            else { COLORS.tile_placeholder_dark };
                 builder.spawn(SpriteBundle {
                     sprite: Sprite {
-                        color: if (x + y) % 2 == 0 { COLORS.tile_placeholder }
-                               else { COLORS.tile_placeholder_dark },
+                        color,
                         custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE )),
                         ..default()},
-                    transform: Transform::from_xyz(
-                        board.cell_position_to_physical(x),
-                        board.cell_position_to_physical(y),
-                        1.0),
+                    transform: Transform::from_translation(
+                        board.cell_position_to_physical(Position { x, y }).extend(1.0)),
             ..default()
         });
     }})
         .insert(board);
 }
 
+#[derive(Component, Debug, Clone, Copy)]
+pub struct SnakeSegment {
+    pub player: usize,
+}
+
 pub struct SpawnSnakeSegment {
+    pub player: usize,
     pub position: Position
 }
 
 impl Command for SpawnSnakeSegment {
     fn apply(self, world: &mut World) {
-        let board = world.query::<&Board>().iter(&world).next().unwrap();
+        let board = world.query::<&Board>().iter(world).next().unwrap();
         world.spawn(
             SpriteBundle {
                 sprite: Sprite {
-                    color: COLORS.snake,
+                    color: COLORS.snakes[self.player % COLORS.snakes.len()],
                     custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
                     ..default()
                 },
-                transform: Transform::from_xyz(
-                    board.cell_position_to_physical(self.position.x),
-                    board.cell_position_to_physical(self.position.y),
-                    2.0
+                transform: Transform::from_translation(
+                    board.cell_position_to_physical(self.position).extend(2.0)
                 ),
                 ..default()
             }
         )
-            .insert(self.position);
+            .insert(self.position)
+            .insert(SnakeSegment { player: self.player });
     }
 }
 
+// the record run raced against, drawn beneath the live snakes and never in their way
+#[derive(Component, Debug, Clone, Copy)]
+pub struct GhostSegment;
+
+pub struct SpawnGhostSegment {
+    pub position: Position,
+}
+
+impl Command for SpawnGhostSegment {
+    fn apply(self, world: &mut World) {
+        let board = world.query::<&Board>().iter(world).next().unwrap();
+        world.spawn(
+            SpriteBundle {
+                sprite: Sprite {
+                    color: COLORS.ghost,
+                    custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_translation(
+                    board.cell_position_to_physical(self.position).extend(1.5)
+                ),
+                ..default()
+            }
+        )
+            .insert(self.position)
+            .insert(GhostSegment);
+    }
+}
 
 pub struct SpawnApple {
     pub position: Position,
@@ -106,27 +181,143 @@ pub struct SpawnApple {
 
 impl Command for SpawnApple {
     fn apply(self, world: &mut World) {
-        let board = world.query::<&Board>().iter(&world).next().unwrap();
-        let food_color = match self.food_type {
-            FoodType::General => {COLORS.food_general}
-            FoodType::SpeedStopper => {COLORS.food_special}
-        };
+        let kind = world.resource::<GameRules>().food.kind(self.food_type);
+        let texture = kind.sprite.as_ref()
+            .map(|path| world.resource::<AssetServer>().load(path.as_str()))
+            .unwrap_or_default();
+        let board = world.query::<&Board>().iter(world).next().unwrap();
         world.spawn(
             SpriteBundle {
                 sprite: Sprite {
-                    color: food_color,
+                    color: if kind.sprite.is_some() { Color::WHITE } else { Color::rgb(kind.color.0, kind.color.1, kind.color.2) },
                     custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
                     ..default()
                 },
-                transform: Transform::from_xyz(
-                    board.cell_position_to_physical(self.position.x),
-                    board.cell_position_to_physical(self.position.y),
-                    2.0
+                texture,
+                transform: Transform::from_translation(
+                    board.cell_position_to_physical(self.position).extend(2.0)
                 ),
                 ..default()
             }
         )
             .insert(self.position)
-            .insert(Food {food_type: self.food_type});
+            .insert(Food {
+                food_type: self.food_type,
+                lifetime: kind.lifetime.map(|total| FoodLifetime { total, remaining: total }),
+            });
+    }
+}
+
+pub struct MoveFood {
+    pub from: Position,
+    pub to: Position,
+}
+
+impl Command for MoveFood {
+    fn apply(self, world: &mut World) {
+        let board = world.query::<&Board>().iter(world).next().unwrap();
+        let translation = board.cell_position_to_physical(self.to).extend(2.0);
+        let mut query_food = world.query_filtered::<(&mut Position, &mut Transform), With<Food>>();
+        if let Some((mut position, mut transform)) = query_food.iter_mut(world).find(|(pos, _)| **pos == self.from) {
+            *position = self.to;
+            transform.translation = translation;
+        }
+    }
+}
+
+// keeps the sprites on the board in step with the sim
+#[derive(SystemParam)]
+pub struct BoardView<'w, 's> {
+    commands: Commands<'w, 's>,
+    boards: Query<'w, 's, (Entity, &'static Board)>,
+    positions: Query<'w, 's, Entity, With<Position>>,
+    segments: Query<'w, 's, (Entity, &'static Position, &'static SnakeSegment)>,
+    food: Query<'w, 's, (Entity, &'static Position, &'static mut Food)>,
+}
+
+impl BoardView<'_, '_> {
+    pub fn apply(&mut self, sim: &SnakeSim, events: &[SimEvent]) {
+        // a ghost can stack segments on one cell, each of them is removed only once
+        let mut removed_segments = vec![];
+        for event in events {
+            match *event {
+                SimEvent::SnakeMoved { player, head, tail } => {
+                    self.commands.add(SpawnSnakeSegment { player, position: head });
+                    if let Some(tail) = tail {
+                        self.remove_segment(player, tail, &mut removed_segments);
+                    }
+                }
+                SimEvent::SegmentRemoved { player, position } => {
+                    self.remove_segment(player, position, &mut removed_segments);
+                }
+                SimEvent::FoodEaten { position, .. } | SimEvent::FoodExpired { position } => {
+                    if let Some((entity, _, _)) = self.food.iter().find(|(_, pos, _)| **pos == position) {
+                        self.commands.entity(entity).despawn_recursive();
+                    }
+                }
+                SimEvent::FoodMoved { from, to } => {
+                    self.commands.add(MoveFood { from, to });
+                }
+                SimEvent::FoodSpawned { position, food_type } => {
+                    self.commands.add(SpawnApple { position, food_type });
+                }
+                SimEvent::SpeedChanged(_) | SimEvent::GameOver(_) => {}
+            }
+        }
+
+        for (_, position, mut food) in self.food.iter_mut() {
+            let expires_in = sim.food.iter()
+                .find(|placed| placed.position == *position)
+                .and_then(|placed| placed.expires_in);
+            if let (Some(lifetime), Some(remaining)) = (food.lifetime.as_mut(), expires_in) {
+                lifetime.remaining = remaining;
+            }
+        }
+    }
+
+    // throws away every sprite and draws the sim from scratch, on a new board if its level differs
+    pub fn redraw(&mut self, sim: &SnakeSim) {
+        for entity in self.positions.iter() {
+            self.commands.entity(entity).despawn_recursive();
+        }
+        match self.boards.get_single() {
+            Ok((_, board)) if board.matches(&sim.level) => {}
+            current => {
+                if let Ok((entity, _)) = current {
+                    self.commands.entity(entity).despawn_recursive();
+                }
+                spawn_board_entity(&mut self.commands, Board::new(&sim.level));
+            }
+        }
+        for (player, snake) in sim.snakes.iter().enumerate() {
+            for position in snake.segments.iter() {
+                self.commands.add(SpawnSnakeSegment { player, position: *position });
+            }
+        }
+        for food in sim.food.iter() {
+            self.commands.add(SpawnApple { position: food.position, food_type: food.food_type });
+        }
+    }
+
+    fn remove_segment(&mut self, player: usize, position: Position, removed_segments: &mut Vec<Entity>) {
+        let of_player = |segment: &SnakeSegment| segment.player == player;
+        remove_segment(&mut self.commands, &self.segments, of_player, position, removed_segments);
+    }
+}
+
+// despawns one segment on `position` that `matches` and wasn't removed before, snakes and ghosts alike
+pub fn remove_segment<T: Component>(
+    commands: &mut Commands,
+    segments: &Query<(Entity, &Position, &T)>,
+    matches: impl Fn(&T) -> bool,
+    position: Position,
+    removed_segments: &mut Vec<Entity>,
+) {
+    let entity = segments.iter()
+        .find(|(entity, pos, segment)| **pos == position && matches(segment) && !removed_segments.contains(entity))
+        .map(|(entity, _, _)| entity);
+    if let Some(entity) = entity {
+        commands.entity(entity).despawn_recursive();
+        removed_segments.push(entity);
     }
 }
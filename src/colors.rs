@@ -4,15 +4,20 @@ pub struct Colors {
     pub board: Color,
     pub tile_placeholder: Color,
     pub tile_placeholder_dark: Color,
-    pub snake: Color,
-    pub food_general: Color,
-    pub food_special: Color,
+    pub wall: Color,
+    pub snakes: [Color; 4],
+    pub ghost: Color,
 }
 pub const COLORS: Colors = Colors {
     board: Color::rgb(0.42, 0.63, 0.07),
     tile_placeholder: Color::rgb(0.62, 0.83, 0.27),
     tile_placeholder_dark: Color::rgb(0.57, 0.78, 0.22),
-    snake: Color::WHITE,
-    food_general: Color::RED,
-    food_special: Color::ORANGE
+    wall: Color::rgb(0.33, 0.27, 0.2),
+    snakes: [
+        Color::WHITE,
+        Color::rgb(0.15, 0.35, 0.9),
+        Color::rgb(0.9, 0.3, 0.6),
+        Color::rgb(0.95, 0.55, 0.1),
+    ],
+    ghost: Color::rgba(1.0, 1.0, 1.0, 0.35),
 };
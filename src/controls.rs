@@ -1,36 +1,151 @@
+use std::collections::VecDeque;
 use bevy::app::App;
-use bevy::prelude::{in_state, Input, IntoSystemConfigs, KeyCode, Plugin, Res, ResMut, Resource, Update};
+use bevy::prelude::{in_state, Component, Input, IntoSystemConfigs, KeyCode, Plugin, Query, Res, Update};
 
-#[derive(Resource)]
-pub enum Direction {
-    Up,
-    Down,
-    Left,
-    Right
-}
+pub use crate::snake::Direction;
 use Direction::*;
 use crate::GameState;
+use crate::sim::SnakeSim;
+
+const MAX_QUEUED_INPUTS: usize = 3;
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct KeyBindings {
+    pub up: KeyCode,
+    pub down: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+}
+
+pub const ARROWS: KeyBindings = KeyBindings { up: KeyCode::Up, down: KeyCode::Down, left: KeyCode::Left, right: KeyCode::Right };
+pub const WASD: KeyBindings = KeyBindings { up: KeyCode::W, down: KeyCode::S, left: KeyCode::A, right: KeyCode::D };
+pub const PLAYER_BINDINGS: [KeyBindings; 2] = [ARROWS, WASD];
+
+impl KeyBindings {
+    fn direction(&self, key: KeyCode) -> Option<Direction> {
+        match key {
+            key if key == self.up => Some(Up),
+            key if key == self.down => Some(Down),
+            key if key == self.left => Some(Left),
+            key if key == self.right => Some(Right),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Player {
+    pub index: usize,
+}
+
+#[derive(Component, Default, Debug)]
+pub struct DirectionQueue {
+    pending: VecDeque<Direction>,
+}
+
+impl DirectionQueue {
+    pub fn push(&mut self, direction: Direction, heading: Direction) {
+        let last = self.pending.back().copied().unwrap_or(heading);
+        if self.pending.len() < MAX_QUEUED_INPUTS && direction != last && direction != last.opposite() {
+            self.pending.push_back(direction);
+        }
+    }
+
+    pub fn next(&mut self, heading: Direction) -> Direction {
+        while let Some(direction) = self.pending.pop_front() {
+            if direction != heading.opposite() {
+                return direction;
+            }
+        }
+        heading
+    }
 
-impl Default for Direction {
-    fn default() -> Self {
-        Right
+    pub fn clear(&mut self) {
+        self.pending.clear();
     }
 }
 
 fn user_input(
     input: Res<Input<KeyCode>>,
-    mut last_pressed: ResMut<Direction>
+    sim: Res<SnakeSim>,
+    mut players: Query<(&Player, &KeyBindings, &mut DirectionQueue)>,
 ) {
-    if input.pressed(KeyCode::Up) { *last_pressed = Up }
-    else if input.pressed(KeyCode::Down) { *last_pressed = Down }
-    else if input.pressed(KeyCode::Left) { *last_pressed = Left }
-    else if input.pressed(KeyCode::Right) { *last_pressed = Right }
+    for (player, bindings, mut queue) in players.iter_mut() {
+        let Some(snake) = sim.snakes.get(player.index) else {
+            continue;
+        };
+        for direction in input.get_just_pressed().filter_map(|key| bindings.direction(*key)) {
+            queue.push(direction, snake.heading);
+        }
+    }
 }
 
 pub struct ControlsPlugin;
 impl Plugin for ControlsPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<Direction>()
-            .add_systems(Update, user_input.run_if(in_state(GameState::Playing)));
+        app.add_systems(Update, user_input.run_if(in_state(GameState::Playing)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reversing_onto_the_body_is_rejected() {
+        let mut queue = DirectionQueue::default();
+        queue.push(Left, Right);
+        assert_eq!(queue.next(Right), Right);
+    }
+
+    #[test]
+    fn repeating_the_heading_is_not_queued() {
+        let mut queue = DirectionQueue::default();
+        queue.push(Right, Right);
+        queue.push(Up, Right);
+        queue.push(Up, Right);
+        assert_eq!(queue.next(Right), Up);
+        assert_eq!(queue.next(Up), Up);
+    }
+
+    #[test]
+    fn quick_turns_are_played_one_per_tick() {
+        let mut queue = DirectionQueue::default();
+        // up then left while heading right is a legal u-turn over two ticks
+        queue.push(Up, Right);
+        queue.push(Left, Right);
+        assert_eq!(queue.next(Right), Up);
+        assert_eq!(queue.next(Up), Left);
+        assert_eq!(queue.next(Left), Left);
+    }
+
+    #[test]
+    fn a_queued_turn_that_became_a_reversal_is_skipped() {
+        let mut queue = DirectionQueue::default();
+        queue.push(Up, Right);
+        queue.push(Left, Right);
+        assert_eq!(queue.next(Right), Up);
+        // the snake is still heading right, so left would turn back onto the body
+        assert_eq!(queue.next(Right), Right);
+    }
+
+    #[test]
+    fn only_a_few_inputs_are_kept() {
+        let mut queue = DirectionQueue::default();
+        for direction in [Up, Left, Down, Right, Up] {
+            queue.push(direction, Right);
+        }
+        assert_eq!(queue.next(Right), Up);
+        assert_eq!(queue.next(Up), Left);
+        assert_eq!(queue.next(Left), Down);
+        assert_eq!(queue.next(Down), Down);
+    }
+
+    #[test]
+    fn clearing_drops_every_input() {
+        let mut queue = DirectionQueue::default();
+        queue.push(Up, Right);
+        queue.clear();
+        assert_eq!(queue.next(Right), Right);
     }
 }
@@ -1,60 +1,86 @@
 use bevy::prelude::*;
-use itertools::Itertools;
-use rand::prelude::SliceRandom;
-use crate::board::{Board, Position, SpawnApple};
-use crate::GameState;
-use crate::snake::Snake;
+use serde::{Deserialize, Serialize};
+use crate::board_is_live;
+use crate::powerup::PowerUp;
 
 
 pub struct FoodPlugin;
 
 impl Plugin for FoodPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<NewFoodEvent>()
-            .add_systems(Update, food_event_listener.run_if(in_state(GameState::Playing)));
+        app.add_systems(Update, animate_expiring_food.run_if(board_is_live));
     }
 }
 
-#[derive(Clone, Copy)]
-pub enum FoodType {
-    General,
-    SpeedStopper,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FoodType(pub usize);
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FoodKind {
+    pub name: String,
+    pub weight: f32,
+    pub score: u32,
+    pub growth: u32,
+    pub color: (f32, f32, f32),
+    #[serde(default)]
+    pub sprite: Option<String>,
+    #[serde(default)]
+    pub effects: Vec<FoodEffect>,
+    #[serde(default)]
+    pub lifetime: Option<u32>,
+}
+
+impl Default for FoodKind {
+    fn default() -> Self {
+        Self {
+            name: "apple".to_string(),
+            weight: 0.0,
+            score: 1,
+            growth: 1,
+            color: (1.0, 0.0, 0.0),
+            sprite: None,
+            effects: vec![],
+            lifetime: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FoodEffect {
+    SpeedUp,
+    ResetSpeed,
+    ShrinkTail(u32),
+    BonusPoints(u32),
+    Invincibility(u32),
+    PowerUp(PowerUp, u32),
 }
 
 #[derive(Component)]
 pub struct Food {
     pub food_type: FoodType,
+    pub lifetime: Option<FoodLifetime>,
 }
 
-#[derive(Event)]
-pub struct NewFoodEvent {
-    pub food_type: FoodType
+#[derive(Debug, Clone, Copy)]
+pub struct FoodLifetime {
+    pub total: u32,
+    pub remaining: u32,
 }
 
-pub fn food_event_listener(
-    mut commands: Commands,
-    query_board: Query<&Board>,
-    mut events:EventReader<NewFoodEvent>,
-    snake: Res<Snake>
-) {
-    let board = query_board.single();
-
-    let possible_food_locations = (0..board.size)
-        .cartesian_product(0..board.size)
-        .map(|point| Position { x: point.0, y: point.1 })
-        .filter(|pos| !snake.segments.contains(pos))
-        .collect::<Vec<Position>>();
-
-    let mut num_food = 0;
-    let mut food_type = FoodType::General;
-    for e in events.iter() {
-        num_food += 1;
-        food_type = e.food_type;
-    }
+const BLINK_BELOW: f32 = 0.3;
+const MIN_FOOD_SCALE: f32 = 0.4;
 
-    let mut rng = rand::thread_rng();
-    for pos in possible_food_locations.choose_multiple(&mut rng, num_food)
-    {
-        commands.add(SpawnApple { position: *pos, food_type });
+fn animate_expiring_food(
+    time: Res<Time>,
+    mut query_food: Query<(&Food, &mut Transform, &mut Visibility)>,
+) {
+    let blink_on = ((time.elapsed_seconds() * 8.0) as u32).is_multiple_of(2);
+    for (food, mut transform, mut visibility) in query_food.iter_mut() {
+        let Some(lifetime) = food.lifetime else {
+            continue;
+        };
+        let left = lifetime.remaining as f32 / lifetime.total.max(1) as f32;
+        transform.scale = Vec3::splat(MIN_FOOD_SCALE + (1.0 - MIN_FOOD_SCALE) * left);
+        *visibility = if left > BLINK_BELOW || blink_on { Visibility::Inherited } else { Visibility::Hidden };
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+
+use crate::board::{remove_segment, GhostSegment, Position, SpawnGhostSegment};
+use crate::net::lockstep::Lockstep;
+use crate::replay::Playback;
+use crate::scoring::HighScore;
+use crate::sim::{SimEvent, SnakeSim};
+use crate::GameState;
+
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Starting), start_ghost.after(crate::reset_game))
+            .add_systems(OnEnter(GameState::Menu), stop_ghost)
+            .add_systems(OnEnter(GameState::Replay), stop_ghost)
+            .add_systems(FixedUpdate, step_ghost
+                .after(crate::tick)
+                .run_if(in_state(GameState::Playing).and_then(resource_exists::<Ghost>())));
+    }
+}
+
+// the record run played alongside the live game, one tick for every live tick
+#[derive(Resource)]
+pub struct Ghost {
+    playback: Playback,
+    sim: SnakeSim,
+    pub score: u32,
+}
+
+// only a solo game on the same board and rules as the record gets a ghost, never a networked one whose sim isn't
+// replaced by the host's round until after this runs
+fn start_ghost(
+    mut commands: Commands,
+    sim: Res<SnakeSim>,
+    high_score: Res<HighScore>,
+    lockstep: Option<Res<Lockstep>>,
+) {
+    commands.remove_resource::<Ghost>();
+    if lockstep.is_some() {
+        return;
+    }
+    let Some(replay) = high_score.replay.as_ref()
+        .filter(|replay| replay.players == 1 && sim.snakes.len() == 1)
+        .filter(|replay| replay.level == sim.level && replay.rules == sim.rules) else {
+        return;
+    };
+
+    let (playback, sim) = Playback::new(replay.clone());
+    for position in sim.snakes[0].segments.iter() {
+        commands.add(SpawnGhostSegment { position: *position });
+    }
+    commands.insert_resource(Ghost { playback, sim, score: 0 });
+}
+
+fn stop_ghost(mut commands: Commands) {
+    commands.remove_resource::<Ghost>();
+}
+
+fn step_ghost(
+    mut commands: Commands,
+    mut ghost: ResMut<Ghost>,
+    segments: Query<(Entity, &Position, &GhostSegment)>,
+) {
+    let Ghost { playback, sim, score } = &mut *ghost;
+    // a ghost that had the ghost power-up can stack segments on one cell, each of them is removed only once
+    let mut removed = vec![];
+    for event in playback.step(sim) {
+        match event {
+            SimEvent::SnakeMoved { head, tail, .. } => {
+                commands.add(SpawnGhostSegment { position: head });
+                if let Some(tail) = tail {
+                    remove_segment(&mut commands, &segments, |_| true, tail, &mut removed);
+                }
+            }
+            SimEvent::SegmentRemoved { position, .. } => {
+                remove_segment(&mut commands, &segments, |_| true, position, &mut removed);
+            }
+            SimEvent::FoodEaten { points, .. } => *score += points,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+use std::fmt;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use bevy::utils::BoxedFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::board::Position;
+use crate::GameState;
+use crate::net::lockstep::Lockstep;
+use crate::settings::Settings;
+
+#[cfg(target_arch = "wasm32")]
+const BUNDLED_LEVELS: [&str; 2] = ["levels/box.level", "levels/cross.level"];
+
+pub struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .init_resource::<Levels>()
+            .add_systems(Startup, load_levels)
+            .add_systems(Update, restart_on_level_change
+                .run_if(in_state(GameState::Playing).or_else(in_state(GameState::Paused)))
+                .run_if(not(resource_exists::<Lockstep>())));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, TypeUuid, TypePath)]
+#[uuid = "a2aa136f-a380-4af9-a3ad-130b238d1259"]
+pub struct Level {
+    pub width: u8,
+    pub height: u8,
+    pub walls: HashSet<Position>,
+    pub start: Option<Position>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LevelError {
+    Empty,
+    TooLarge,
+    RaggedRow { row: usize },
+    UnknownTile { row: usize, column: usize, tile: char },
+    MultipleStarts,
+    OffTheBoard { x: u8, y: u8 },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Empty => write!(f, "level has no tiles"),
+            LevelError::TooLarge => write!(f, "level is larger than 255 tiles in some direction"),
+            LevelError::RaggedRow { row } => write!(f, "row {} has a different length than the first row", row + 1),
+            LevelError::UnknownTile { row, column, tile } =>
+                write!(f, "unknown tile `{}` at row {}, column {}", tile, row + 1, column + 1),
+            LevelError::MultipleStarts => write!(f, "level has more than one `S` start tile"),
+            LevelError::OffTheBoard { x, y } => write!(f, "tile {},{} is off the board", x, y),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl Level {
+    pub fn open(width: u8, height: u8) -> Self {
+        Self {
+            width,
+            height,
+            walls: HashSet::new(),
+            start: None,
+        }
+    }
+
+    pub fn parse(source: &str) -> Result<Self, LevelError> {
+        let rows = source.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        let width = rows.first().ok_or(LevelError::Empty)?.chars().count();
+        let height = rows.len();
+        if width > u8::MAX as usize || height > u8::MAX as usize {
+            return Err(LevelError::TooLarge);
+        }
+
+        let mut walls = HashSet::new();
+        let mut start = None;
+        for (row, line) in rows.iter().enumerate() {
+            if line.chars().count() != width {
+                return Err(LevelError::RaggedRow { row });
+            }
+            // the first row of the file is the top of the board
+            let y = (height - 1 - row) as u8;
+            for (column, tile) in line.chars().enumerate() {
+                let position = Position { x: column as u8, y };
+                match tile {
+                    '#' => { walls.insert(position); }
+                    'S' if start.is_some() => return Err(LevelError::MultipleStarts),
+                    'S' => start = Some(position),
+                    '.' => {}
+                    _ => return Err(LevelError::UnknownTile { row, column, tile }),
+                }
+            }
+        }
+
+        Ok(Self { width: width as u8, height: height as u8, walls, start })
+    }
+
+    // what `parse` guarantees, for levels that come from elsewhere: replays, the network and spectated games
+    pub fn check(&self) -> Result<(), LevelError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(LevelError::Empty);
+        }
+        match self.walls.iter().chain(self.start.iter()).find(|position| !self.contains(**position)) {
+            Some(position) => Err(LevelError::OffTheBoard { x: position.x, y: position.y }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn contains(&self, position: Position) -> bool {
+        position.x < self.width && position.y < self.height
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.width as usize * self.height as usize - self.walls.len()
+    }
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let level = Level::parse(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level"]
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Levels {
+    entries: Vec<(String, Handle<Level>)>,
+}
+
+impl Levels {
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(path, _)| path.as_str())
+    }
+
+    pub fn current(&self, settings: &Settings, assets: &Assets<Level>) -> Level {
+        settings.level.as_ref()
+            .and_then(|path| self.entries.iter().find(|(entry, _)| entry == path))
+            .and_then(|(_, handle)| assets.get(handle))
+            .cloned()
+            .unwrap_or_else(|| Level::open(settings.board_width, settings.board_height))
+    }
+
+    fn is_current(&self, settings: &Settings, handle: &Handle<Level>) -> bool {
+        self.entries.iter()
+            .any(|(path, entry)| entry == handle && settings.level.as_ref() == Some(path))
+    }
+}
+
+pub fn level_name(path: &str) -> &str {
+    std::path::Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(path)
+}
+
+fn load_levels(
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    mut levels: ResMut<Levels>,
+) {
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut paths = match asset_server.load_folder("levels") {
+        Ok(handles) => handles.iter()
+            .filter_map(|handle| asset_server.get_handle_path(handle))
+            .map(|path| path.path().to_string_lossy().replace('\\', "/"))
+            .filter(|path| path.ends_with(".level"))
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            warn!("could not load levels folder: {}", err);
+            vec![]
+        }
+    };
+    #[cfg(target_arch = "wasm32")]
+    let mut paths = BUNDLED_LEVELS.iter().map(|path| path.to_string()).collect::<Vec<_>>();
+
+    if let Some(path) = &settings.level {
+        if !paths.contains(path) {
+            paths.push(path.clone());
+        }
+    }
+    paths.sort();
+
+    levels.entries = paths.into_iter()
+        .map(|path| {
+            let handle = asset_server.load(path.as_str());
+            (path, handle)
+        })
+        .collect();
+}
+
+// a networked game is played on the host's level and would only fall out of step by restarting on its own
+fn restart_on_level_change(
+    mut events: EventReader<AssetEvent<Level>>,
+    levels: Res<Levels>,
+    settings: Res<Settings>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let changed = events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => levels.is_current(&settings, handle),
+        AssetEvent::Removed { .. } => false,
+    });
+    if changed {
+        next_state.set(GameState::Starting);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_walls_and_start_with_the_first_row_on_top() {
+        let level = Level::parse("
+            #..
+            .S.
+            ..#
+        ").unwrap();
+        assert_eq!((level.width, level.height), (3, 3));
+        assert_eq!(level.walls, HashSet::from([Position { x: 0, y: 2 }, Position { x: 2, y: 0 }]));
+        assert_eq!(level.start, Some(Position { x: 1, y: 1 }));
+        assert_eq!(level.cell_count(), 7);
+    }
+
+    #[test]
+    fn an_empty_file_is_rejected() {
+        assert_eq!(Level::parse(""), Err(LevelError::Empty));
+        assert_eq!(Level::parse("\n   \n"), Err(LevelError::Empty));
+    }
+
+    #[test]
+    fn rows_must_have_the_same_length() {
+        assert_eq!(Level::parse("...\n..\n..."), Err(LevelError::RaggedRow { row: 1 }));
+    }
+
+    #[test]
+    fn unknown_tiles_are_reported_where_they_are() {
+        assert_eq!(Level::parse("...\n.x."), Err(LevelError::UnknownTile { row: 1, column: 1, tile: 'x' }));
+    }
+
+    #[test]
+    fn only_one_start_is_allowed() {
+        assert_eq!(Level::parse("S..\n..S"), Err(LevelError::MultipleStarts));
+    }
+
+    #[test]
+    fn boards_over_255_tiles_are_rejected() {
+        assert_eq!(Level::parse(&".".repeat(256)), Err(LevelError::TooLarge));
+        assert_eq!(Level::parse(&".\n".repeat(256)), Err(LevelError::TooLarge));
+        assert!(Level::parse(&".".repeat(255)).is_ok());
+    }
+
+    #[test]
+    fn levels_from_elsewhere_are_checked_like_parsed_ones() {
+        assert_eq!(Level::open(0, 4).check(), Err(LevelError::Empty));
+        let level = Level { start: Some(Position { x: 4, y: 1 }), ..Level::open(4, 4) };
+        assert_eq!(level.check(), Err(LevelError::OffTheBoard { x: 4, y: 1 }));
+        let level = Level { walls: HashSet::from([Position { x: 0, y: 9 }]), ..Level::open(4, 4) };
+        assert_eq!(level.check(), Err(LevelError::OffTheBoard { x: 0, y: 9 }));
+        assert_eq!(Level::parse("#.\n.S").unwrap().check(), Ok(()));
+    }
+}
@@ -1,10 +1,18 @@
-use std::time::Duration;
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
 use bevy::prelude::*;
-use rand::Rng;
-use crate::board::{Board, Position, SpawnSnakeSegment};
-use crate::food::{Food, FoodType, NewFoodEvent};
+use crate::board::BoardView;
+use crate::ai::AiController;
+use crate::autopilot::Autopilot;
+use crate::controls::{DirectionQueue, Player, PLAYER_BINDINGS};
+use crate::level::{Level, Levels};
+use crate::net::lockstep::Lockstep;
+use crate::replay::{Recorder, Replay};
+use crate::rng::GameRng;
+use crate::rules::GameRules;
 use crate::scoring::Score;
-use crate::snake::Snake;
+use crate::settings::Settings;
+use crate::sim::{GameOverReason, SimEvent, SnakeSim};
 
 pub mod board;
 pub mod colors;
@@ -13,155 +21,195 @@ pub mod food;
 pub mod controls;
 pub mod ui;
 pub mod scoring;
+pub mod sim;
+pub mod rng;
+pub mod settings;
+pub mod level;
+pub mod rules;
+pub mod powerup;
+pub mod ai;
+pub mod autopilot;
+pub mod attract;
+pub mod replay;
+pub mod ghost;
+pub mod net;
+pub mod args;
+#[cfg(feature = "spectator")]
+pub mod spectator;
 
 
 #[derive(States, Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
 pub enum GameState {
     Menu,
     #[default]
+    Starting,
     Playing,
+    Paused,
+    GameOver,
+    // a computer game running behind the main menu
+    Demo,
+    Replay,
+    // showing a game streamed from another instance
+    Spectating,
 }
 
-#[derive(PartialEq, Eq, Debug)]
-enum GameOverReason {
-    HitWall,
-    HitSnake,
-    Win,
+#[derive(Event, Debug, Clone, Copy)]
+pub struct GameOver {
+    pub reason: GameOverReason,
 }
 
-#[derive(Resource)]
-pub struct Countdown {
-    pub main_timer: Timer,
-    pub default_duration: Duration,
-    pub change_step_duration: Duration,
-    pub min_duration: Duration,
-}
+// a line for the player about something that happened outside the board, like saving a replay
+#[derive(Event, Debug, Clone)]
+pub struct Notice(pub String);
 
-impl Countdown {
-    pub fn new(duration_millis: u32, min_duration_millis: u64, change_step_millis: u64) -> Self {
-        Self {
-            main_timer: Timer::from_seconds(duration_millis as f32 / 1000.0, TimerMode::Repeating),
-            default_duration: Duration::from_millis(duration_millis as u64),
-            change_step_duration: Duration::from_millis(change_step_millis),
-            min_duration: Duration::from_millis(min_duration_millis),
-        }
+pub struct MainPlugin;
+impl Plugin for MainPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FixedTime::new(sim::Speed::default().current))
+            .init_resource::<SnakeSim>()
+            .init_resource::<GameRng>()
+            .init_resource::<Settings>()
+            .init_resource::<Recorder>()
+            .add_event::<GameOver>()
+            .add_event::<Notice>()
+            .add_systems(OnEnter(GameState::Starting), start_playing)
+            .add_systems(FixedUpdate, tick.run_if(game_is_running));
     }
 }
 
-impl Default for Countdown {
-    fn default() -> Self {
-        Self::new(400, 100, 50)
-    }
+// snakes are steered and stepped both in a real game and in the demo behind the menu
+pub fn game_is_running(state: Res<State<GameState>>) -> bool {
+    matches!(state.get(), GameState::Playing | GameState::Demo)
 }
 
-pub struct MainPlugin;
-impl Plugin for MainPlugin {
-    fn build(&self, app: &mut App) {
-        app.init_resource::<Countdown>()
-            .add_systems(Update, tick.run_if(in_state(GameState::Playing)));
-    }
+// the board also changes while a replay is played back or a remote game is watched
+pub fn board_is_live(state: Res<State<GameState>>) -> bool {
+    matches!(state.get(), GameState::Playing | GameState::Demo | GameState::Replay | GameState::Spectating)
+}
+
+fn start_playing(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::Playing);
 }
 
 pub fn tick(
-    mut commands: Commands,
-    time: Res<Time>,
-    mut countdown: ResMut<Countdown>,
-    mut snake: ResMut<Snake>,
-    positions: Query<(Entity, &Position)>,
-    input: Res<controls::Direction>,
-    query_food: Query<(Entity, &Position, &Food), With<Food>>,
-    mut food_event: EventWriter<NewFoodEvent>,
-    query_board: Query<&Board>,
+    mut board: BoardView,
+    mut fixed_time: ResMut<FixedTime>,
+    mut sim: ResMut<SnakeSim>,
+    mut players: Query<(&Player, &mut DirectionQueue)>,
+    mut rng: ResMut<GameRng>,
     mut next_state: ResMut<NextState<GameState>>,
-    mut score: ResMut<Score>
+    mut game_over: EventWriter<GameOver>,
+    mut score: ResMut<Score>,
+    mut recorder: ResMut<Recorder>,
+    mut lockstep: Option<ResMut<Lockstep>>,
+    state: Res<State<GameState>>,
 ) {
-    countdown.main_timer.tick(time.delta());
-    if !countdown.main_timer.finished() {
-        return;
-    }
-
-    let board = query_board.single();
-
-    let mut next_position = snake.segments[0].clone();
-    let hit_wall = match *input {
-        controls::Direction::Up if next_position.y == board.size - 1 => { Some(GameOverReason::HitWall) }
-        controls::Direction::Down if next_position.y == 0 => { Some(GameOverReason::HitWall) }
-        controls::Direction::Left if next_position.x == 0 => { Some(GameOverReason::HitWall) }
-        controls::Direction::Right if next_position.x == board.size - 1 => { Some(GameOverReason::HitWall) }
-        controls::Direction::Up => { next_position.y += 1; None }
-        controls::Direction::Down => { next_position.y -= 1; None }
-        controls::Direction::Left => { next_position.x -= 1; None }
-        controls::Direction::Right => { next_position.x += 1; None }
-    };
-    let hit_self = if snake.segments.contains(&next_position) {
-        Some(GameOverReason::HitSnake)
-    } else { None };
-    let has_won = if snake.segments.len() == board.size as usize * board.size as usize {
-        Some(GameOverReason::Win)
-    } else {
-        None
+    let demo = *state.get() == GameState::Demo;
+    let inputs = match lockstep.as_deref_mut() {
+        // a networked game only moves on once both peers' inputs for the tick are in
+        Some(lockstep) => match lockstep.ready.take() {
+            Some(inputs) => inputs,
+            None => return,
+        },
+        None => {
+            let mut inputs = sim.snakes.iter().map(|snake| snake.heading).collect::<Vec<_>>();
+            for (player, mut queue) in players.iter_mut() {
+                if let Some(input) = inputs.get_mut(player.index) {
+                    *input = queue.next(*input);
+                }
+            }
+            inputs
+        }
     };
-
-    if hit_wall.is_some() || hit_self.is_some() || has_won.is_some() {
-        next_state.set(GameState::Menu);
-        return;
+    if !demo {
+        recorder.record(&inputs);
     }
 
-    snake.segments.push_front(next_position);
-
-    commands.add(SpawnSnakeSegment {position: next_position});
-    let is_food = query_food.iter().find(|(_, pos, _)| &&next_position == pos );
-    match is_food {
-        None => {
-            let old_tail = snake.segments.pop_back().unwrap();
-            if let Some((entry, _)) = positions.iter().find(|(_, pos)| pos == &&old_tail) {
-                commands.entity(entry).despawn_recursive();
+    let rng = match lockstep.as_deref_mut() {
+        Some(lockstep) => &mut lockstep.rng,
+        None => &mut *rng,
+    };
+    let events = sim.step(&inputs, rng);
+    board.apply(&sim, &events);
+    for event in events {
+        match event {
+            SimEvent::FoodEaten { player, points, .. } if !demo => {
+                score.add(player, points);
             }
-        }
-        Some((entity, _, food)) => {
-            commands.entity(entity).despawn_recursive();
-            score.score += 1;
-            let mut rng = rand::thread_rng();
-            let v: f32 = rng.gen();
-            let new_food = if v > 0.7 { FoodType::SpeedStopper } else { FoodType::General };
-            food_event.send(NewFoodEvent {food_type: new_food});
-
-            match food.food_type {
-                FoodType::SpeedStopper => {
-                    let def_duration = countdown.default_duration;
-                    countdown.main_timer.set_duration(def_duration);
-                }
-                FoodType::General => {
-                        let current_duration = countdown.main_timer.duration();
-                        if current_duration > countdown.min_duration {
-                            let new_duration = current_duration - countdown.change_step_duration;
-                            countdown.main_timer.set_duration(new_duration);
-                    }
-                }
+            SimEvent::SpeedChanged(duration) => {
+                fixed_time.period = duration;
             }
+            SimEvent::GameOver(_) if demo => {
+                next_state.set(GameState::Menu);
+            }
+            SimEvent::GameOver(reason) => {
+                game_over.send(GameOver { reason });
+                next_state.set(GameState::GameOver);
+            }
+            _ => {}
         }
     }
 }
 
 pub fn reset_game(
     mut commands: Commands,
-    mut snake: ResMut<Snake>,
-    positions: Query<Entity, With<Position>>,
-    mut last_pressed: ResMut<controls::Direction>,
-    mut food_events: EventWriter<NewFoodEvent>,
+    mut board: BoardView,
+    mut sim: ResMut<SnakeSim>,
+    query_players: Query<Entity, With<Player>>,
     mut score: ResMut<Score>,
-    mut countdown: ResMut<Countdown>,
+    mut fixed_time: ResMut<FixedTime>,
+    mut rng: ResMut<GameRng>,
+    mut recorder: ResMut<Recorder>,
+    settings: Res<Settings>,
+    levels: Res<Levels>,
+    level_assets: Res<Assets<Level>>,
+    rules: Res<GameRules>,
 ) {
-    for entity in positions.iter() {
+    let humans = settings.players.clamp(1, settings::MAX_PLAYERS);
+    let players = (humans + settings.opponents).min(settings::MAX_SNAKES);
+    let level = levels.current(&settings, &level_assets);
+    if let Some(start) = rules.start.position.filter(|start| level.start.is_none() && !level.contains(*start)) {
+        warn!("the start {},{} in rules.ron is off the {}x{} board, the snakes start in the corners",
+            start.x, start.y, level.width, level.height);
+    }
+    let round = SnakeSim::new(level, rules.with_settings(&settings), players).with_opponents(players - humans);
+    let entities = start_round(&mut commands, &mut board, &query_players, &mut fixed_time, &mut rng, &mut sim, round);
+    for (player, entity) in entities.into_iter().enumerate() {
+        let mut entity = commands.entity(entity);
+        match PLAYER_BINDINGS.get(player).filter(|_| player < humans) {
+            Some(bindings) => entity.insert(*bindings),
+            None => entity.insert(AiController { difficulty: settings.difficulty }),
+        };
+        if settings.autopilot && player == 0 {
+            entity.insert(Autopilot::new(&sim.level));
+        }
+    }
+
+    *score = Score::new(players);
+    recorder.replay = Some(Replay::new(rng.seed(), &sim));
+}
+
+// puts `round` in play and redraws the board for it, returning one player entity per snake for the caller to equip
+pub fn start_round(
+    commands: &mut Commands,
+    board: &mut BoardView,
+    players: &Query<Entity, With<Player>>,
+    fixed_time: &mut FixedTime,
+    rng: &mut GameRng,
+    sim: &mut SnakeSim,
+    round: SnakeSim,
+) -> Vec<Entity> {
+    for entity in players.iter() {
         commands.entity(entity).despawn_recursive();
     }
 
-    let def_duration = countdown.default_duration;
-    countdown.main_timer.set_duration(def_duration);
+    rng.restart();
+    *sim = round;
+    sim.reset(rng);
+    board.redraw(sim);
 
-    food_events.send(NewFoodEvent {food_type: FoodType::General});
-    *snake = Default::default();
-    *last_pressed = Default::default();
-    *score = Default::default();
+    *fixed_time = FixedTime::new(sim.period());
+    (0..sim.snakes.len())
+        .map(|player| commands.spawn((Player { index: player }, DirectionQueue::default())).id())
+        .collect()
 }
-
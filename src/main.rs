@@ -1,20 +1,43 @@
 use bevy::prelude::*;
-use snake::board::spawn_board;
+use snake::ai::AiPlugin;
+use snake::args::{arg_value, port_from_args, settings_from_args};
+use snake::attract::AttractPlugin;
+use snake::replay::{ReplayPlugin, ReplayRequest};
+use snake::autopilot::AutopilotPlugin;
+use snake::board::{respawn_board, spawn_board};
 use snake::controls::ControlsPlugin;
 use snake::food::FoodPlugin;
-use snake::snake::Snake;
+use snake::ghost::GhostPlugin;
+use snake::level::{Level, LevelPlugin};
+use snake::net::lockstep::Lockstep;
+use snake::net::{NetConfig, NetPlugin, NetRequest};
+use snake::rules::RulesPlugin;
 use snake::{GameState, MainPlugin, reset_game};
+use snake::rng::GameRng;
+use snake::settings::Settings;
 use snake::scoring::ScorePlugin;
 use snake::ui::hud::HudPlugin;
 use snake::ui::menu::UiPlugin;
+use snake::ui::game_over::GameOverPlugin;
+use snake::ui::pause::PausePlugin;
+use snake::ui::replay::ReplayUiPlugin;
+use snake::ui::notice::NoticePlugin;
+#[cfg(feature = "spectator")]
+use snake::spectator::{SpectatorConfig, SpectatorPlugin};
 
 
 fn main() {
-    App::new()
-        .add_state::<GameState>()
+    let mut app = App::new();
+    app.add_state::<GameState>()
         .add_systems(Startup, setup)
         .add_systems(Startup, spawn_board)
-        .insert_resource::<Snake>(Default::default())
+        .add_systems(Startup, load_replay_from_args)
+        .add_systems(Startup, connect_from_args)
+        .add_systems(Update, respawn_board
+            .run_if(resource_changed::<Settings>().or_else(on_event::<AssetEvent<Level>>())))
+        .insert_resource(arg_value("--seed").and_then(|seed| seed.parse().ok()).map(GameRng::from_seed).unwrap_or_default())
+        .insert_resource(settings_from_args())
+        .insert_resource(net_config_from_args())
         .insert_resource(ClearColor(Color::rgb(0.2, 0.5, 0.7)))
         .add_plugins(DefaultPlugins.set(
             WindowPlugin {
@@ -26,18 +49,78 @@ fn main() {
                 }),
                 ..default()
             }
+        ).set(
+            AssetPlugin {
+                #[cfg(not(target_arch = "wasm32"))]
+                watch_for_changes: bevy::asset::ChangeWatcher::with_delay(std::time::Duration::from_millis(200)),
+                ..default()
+            }
         ))
+        .add_plugins(LevelPlugin)
+        .add_plugins(RulesPlugin)
         .add_plugins(ControlsPlugin)
+        .add_plugins(AiPlugin)
+        .add_plugins(AutopilotPlugin)
+        .add_plugins(AttractPlugin)
+        .add_plugins(ReplayPlugin)
+        .add_plugins(ReplayUiPlugin)
+        .add_plugins(NoticePlugin)
+        .add_plugins(GhostPlugin)
+        .add_plugins(NetPlugin)
         .add_plugins(FoodPlugin)
         .add_plugins(UiPlugin)
         .add_plugins(HudPlugin)
+        .add_plugins(GameOverPlugin)
+        .add_plugins(PausePlugin)
         .add_plugins(ScorePlugin)
         .add_plugins(MainPlugin)
-        .add_systems(OnEnter(GameState::Playing), reset_game)
-        .run();
+        .add_systems(OnEnter(GameState::Starting), reset_game.run_if(not(resource_exists::<Lockstep>())));
+
+    #[cfg(feature = "spectator")]
+    app.add_plugins(SpectatorPlugin)
+        .insert_resource(spectator_config_from_args());
+
+    app.run();
 }
 
 fn setup(mut commands: Commands) {
     commands
         .spawn(Camera2dBundle::default());
 }
+
+fn load_replay_from_args(mut requests: EventWriter<ReplayRequest>) {
+    if let Some(path) = arg_value("--replay") {
+        requests.send(ReplayRequest::Load(Some(path.into())));
+    }
+}
+
+// `--host` waits for the other player right away, `--join 192.168.0.2:7777` connects to one
+fn connect_from_args(mut requests: EventWriter<NetRequest>) {
+    if std::env::args().any(|arg| arg == "--host") {
+        requests.send(NetRequest::Host);
+    } else if arg_value("--join").is_some() {
+        requests.send(NetRequest::Join);
+    }
+}
+
+fn net_config_from_args() -> NetConfig {
+    let mut config = NetConfig::default();
+    if let Some(port) = port_from_args() {
+        config.port = port;
+    }
+    if let Some(address) = arg_value("--join") {
+        config.address = address;
+    }
+    config
+}
+
+// `--spectator-port 7781` streams on another port, `--spectate ws://192.168.0.2:7780` watches a stream
+#[cfg(feature = "spectator")]
+fn spectator_config_from_args() -> SpectatorConfig {
+    let mut config = SpectatorConfig::default();
+    if let Some(port) = arg_value("--spectator-port").and_then(|port| port.parse().ok()) {
+        config.port = port;
+    }
+    config.watch = arg_value("--spectate");
+    config
+}
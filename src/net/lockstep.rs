@@ -0,0 +1,378 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
+
+use crate::level::Level;
+use crate::rng::GameRng;
+use crate::rules::GameRules;
+use crate::sim::SnakeSim;
+use crate::snake::Direction;
+
+pub const DEFAULT_PORT: u16 = 7777;
+pub const PLAYERS: usize = 2;
+// ticks between reading a key and playing it, time for the input to reach the other peer
+pub const INPUT_DELAY: u64 = 3;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Message {
+    Hello { version: String },
+    // sent by the host or the server once both sides said hello, everything the other side needs to run the same sim
+    Start { seed: u64, level: Level, rules: GameRules, player: usize },
+    Input { tick: u64, direction: Direction },
+    // state hash before the tick is played
+    Hash { tick: u64, hash: u64 },
+}
+
+impl Message {
+    pub fn hello() -> Self {
+        Message::Hello { version: crate::replay::file::GAME_VERSION.to_string() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetError {
+    Io(String),
+    Disconnected,
+    Protocol(String),
+    Version { peer: String },
+    Desync { tick: u64 },
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::Io(err) => write!(f, "network error: {}", err),
+            NetError::Disconnected => write!(f, "the other player left"),
+            NetError::Protocol(err) => write!(f, "the other player sent something unexpected: {}", err),
+            NetError::Version { peer } =>
+                write!(f, "the other player runs snake {}, this is snake {}", peer, crate::replay::file::GAME_VERSION),
+            NetError::Desync { tick } => write!(f, "the games went out of sync at tick {}", tick),
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
+
+impl From<std::io::Error> for NetError {
+    fn from(err: std::io::Error) -> Self {
+        NetError::Io(err.to_string())
+    }
+}
+
+pub fn listen(port: u16) -> Result<TcpListener, NetError> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+pub fn connect(address: &str) -> Result<Connection, NetError> {
+    let address = address.to_socket_addrs()?
+        .next()
+        .ok_or_else(|| NetError::Io(format!("`{}` is not an address", address)))?;
+    Connection::new(TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?)
+}
+
+// a TCP stream carrying one message per line of ron, polled without ever blocking
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    closed: bool,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> Result<Self, NetError> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self { stream, incoming: vec![], outgoing: vec![], closed: false })
+    }
+
+    pub fn send(&mut self, message: &Message) {
+        self.outgoing.extend(ron::to_string(message).expect("messages serialize to ron").bytes());
+        self.outgoing.push(b'\n');
+    }
+
+    // writes what the socket takes and returns the messages that arrived since the last poll
+    pub fn poll(&mut self) -> Result<Vec<Message>, NetError> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(NetError::Disconnected),
+                Ok(written) => { self.outgoing.drain(..written); }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let mut buffer = [0; 4096];
+        while !self.closed {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(read) => self.incoming.extend(&buffer[..read]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let mut messages = vec![];
+        while let Some(end) = self.incoming.iter().position(|byte| *byte == b'\n') {
+            let line = self.incoming.drain(..=end).collect::<Vec<_>>();
+            messages.push(ron::de::from_bytes(&line[..end]).map_err(|err| NetError::Protocol(err.to_string()))?);
+        }
+        // whatever the peer sent before hanging up is still handed out first
+        if messages.is_empty() && self.closed {
+            return Err(NetError::Disconnected);
+        }
+        Ok(messages)
+    }
+}
+
+// every player's inputs and state hashes for the ticks not settled yet; a tick's hashes are compared once each slot
+// sent one, a server that plays along takes a slot of its own
+pub struct Ledger {
+    inputs: BTreeMap<u64, [Option<Direction>; PLAYERS]>,
+    hashes: BTreeMap<u64, Vec<Option<u64>>>,
+    slots: usize,
+}
+
+impl Ledger {
+    pub fn new(slots: usize) -> Self {
+        Self { inputs: BTreeMap::new(), hashes: BTreeMap::new(), slots }
+    }
+
+    // a message `player` sent during the game
+    pub fn handle(&mut self, player: usize, message: Message) -> Result<(), NetError> {
+        match message {
+            Message::Input { tick, direction } => {
+                self.record_input(tick, player, direction);
+                Ok(())
+            }
+            Message::Hash { tick, hash } => self.record_hash(tick, player, hash),
+            Message::Hello { .. } | Message::Start { .. } => Err(NetError::Protocol("a handshake during the game".to_string())),
+        }
+    }
+
+    pub fn record_input(&mut self, tick: u64, player: usize, direction: Direction) {
+        self.inputs.entry(tick).or_default()[player] = Some(direction);
+    }
+
+    // every snake's input for `tick`, once all of them arrived
+    pub fn take_inputs(&mut self, tick: u64) -> Option<Vec<Direction>> {
+        let inputs = self.inputs.get(&tick)?.iter().copied().collect::<Option<Vec<_>>>()?;
+        self.inputs.remove(&tick);
+        Some(inputs)
+    }
+
+    pub fn record_hash(&mut self, tick: u64, slot: usize, hash: u64) -> Result<(), NetError> {
+        let hashes = self.hashes.entry(tick).or_insert_with(|| vec![None; self.slots]);
+        hashes[slot] = Some(hash);
+        if hashes.iter().all(Option::is_some) {
+            let hashes = self.hashes.remove(&tick).unwrap_or_default();
+            if hashes.iter().any(|other| *other != hashes[0]) {
+                return Err(NetError::Desync { tick });
+            }
+        }
+        Ok(())
+    }
+}
+
+// both peers play a tick only once they have each other's input for it, so their sims never diverge
+#[derive(Resource)]
+pub struct Lockstep {
+    connection: Connection,
+    pub local: usize,
+    pub seed: u64,
+    pub level: Level,
+    pub rules: GameRules,
+    pub rng: GameRng,
+    // the next tick to play, the first one without a local input and the first one without a local hash
+    tick: u64,
+    sent: u64,
+    hashed: u64,
+    last_sent: Direction,
+    ledger: Ledger,
+    // every snake's input for the tick about to be played, taken by `tick`
+    pub ready: Option<Vec<Direction>>,
+}
+
+impl Lockstep {
+    pub fn new(connection: Connection, local: usize, seed: u64, level: Level, rules: GameRules) -> Self {
+        let heading = SnakeSim::new(level.clone(), rules.clone(), PLAYERS).snakes[local].heading;
+        Self {
+            connection,
+            local,
+            seed,
+            level,
+            rules,
+            rng: GameRng::from_seed(seed),
+            tick: 0,
+            sent: 0,
+            hashed: 0,
+            last_sent: heading,
+            ledger: Ledger::new(PLAYERS),
+            ready: None,
+        }
+    }
+
+    // `hash` is the state the next tick starts from and `next_input` turns the heading the local snake will have
+    // by the time its input is played into that input; returns every snake's input once the peer's has arrived
+    pub fn exchange(
+        &mut self,
+        hash: u64,
+        mut next_input: impl FnMut(Direction) -> Direction,
+    ) -> Result<Option<Vec<Direction>>, NetError> {
+        if self.hashed == self.tick {
+            self.connection.send(&Message::Hash { tick: self.tick, hash });
+            self.ledger.record_hash(self.tick, self.local, hash)?;
+            self.hashed += 1;
+        }
+        while self.sent <= self.tick + INPUT_DELAY {
+            let direction = next_input(self.last_sent);
+            self.connection.send(&Message::Input { tick: self.sent, direction });
+            self.ledger.record_input(self.sent, self.local, direction);
+            self.last_sent = direction;
+            self.sent += 1;
+        }
+
+        // a peer that hung up after its last game over tick still left the inputs to play up to it
+        let polled = self.connection.poll();
+        for message in polled.clone().unwrap_or_default() {
+            self.handle(message)?;
+        }
+
+        let Some(inputs) = self.ledger.take_inputs(self.tick) else {
+            return polled.map(|_| None);
+        };
+        self.tick += 1;
+        Ok(Some(inputs))
+    }
+
+    pub fn handle(&mut self, message: Message) -> Result<(), NetError> {
+        self.ledger.handle(1 - self.local, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use super::*;
+
+    fn pair() -> (Lockstep, Lockstep) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let guest = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (host, _) = listener.accept().unwrap();
+        let (level, rules) = (Level::open(12, 12), GameRules::default());
+        (
+            Lockstep::new(Connection::new(host).unwrap(), 0, 42, level.clone(), rules.clone()),
+            Lockstep::new(Connection::new(guest).unwrap(), 1, 42, level, rules),
+        )
+    }
+
+    // keeps both sides exchanging until each has played its next tick or one of them fails
+    fn exchange_both(
+        host: &mut Lockstep,
+        guest: &mut Lockstep,
+        hashes: [u64; PLAYERS],
+        inputs: [Direction; PLAYERS],
+    ) -> Result<[Vec<Direction>; PLAYERS], NetError> {
+        let (mut played_host, mut played_guest) = (None, None);
+        for _ in 0..1000 {
+            if played_host.is_none() {
+                played_host = host.exchange(hashes[0], |_| inputs[0])?;
+            }
+            if played_guest.is_none() {
+                played_guest = guest.exchange(hashes[1], |_| inputs[1])?;
+            }
+            if let (Some(host), Some(guest)) = (&played_host, &played_guest) {
+                return Ok([host.clone(), guest.clone()]);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        panic!("the peers never agreed on a tick");
+    }
+
+    #[test]
+    fn both_peers_play_the_same_inputs_after_the_delay() {
+        let (mut host, mut guest) = pair();
+        // the first exchange fills the delay with the keys read then, later keys come INPUT_DELAY ticks after
+        let played = (0..=INPUT_DELAY + 1)
+            .map(|tick| {
+                let keys = if tick == 0 { [Direction::Up, Direction::Down] } else { [Direction::Left, Direction::Right] };
+                let [first, second] = exchange_both(&mut host, &mut guest, [7, 7], keys).unwrap();
+                assert_eq!(first, second);
+                first
+            })
+            .collect::<Vec<_>>();
+        for inputs in played[..=INPUT_DELAY as usize].iter() {
+            assert_eq!(*inputs, vec![Direction::Up, Direction::Down]);
+        }
+        assert_eq!(played[INPUT_DELAY as usize + 1], vec![Direction::Left, Direction::Right]);
+    }
+
+    #[test]
+    fn a_tick_waits_for_the_other_peer() {
+        let (mut host, _guest) = pair();
+        for _ in 0..10 {
+            assert_eq!(host.exchange(7, |heading| heading), Ok(None));
+        }
+        assert_eq!(host.tick, 0);
+        assert_eq!(host.sent, INPUT_DELAY + 1);
+    }
+
+    #[test]
+    fn different_hashes_are_a_desync() {
+        let (mut host, mut guest) = pair();
+        exchange_both(&mut host, &mut guest, [1, 1], [Direction::Up; PLAYERS]).unwrap();
+        let err = exchange_both(&mut host, &mut guest, [2, 3], [Direction::Up; PLAYERS]).unwrap_err();
+        assert_eq!(err, NetError::Desync { tick: 1 });
+    }
+
+    #[test]
+    fn hashes_are_compared_once_every_slot_sent_one() {
+        let mut ledger = Ledger::new(PLAYERS + 1);
+        assert_eq!(ledger.record_hash(4, 1, 9), Ok(()));
+        assert_eq!(ledger.record_hash(5, 0, 9), Ok(()));
+        assert_eq!(ledger.record_hash(4, 0, 9), Ok(()));
+        assert_eq!(ledger.record_hash(4, 2, 9), Ok(()));
+        assert!(!ledger.hashes.contains_key(&4));
+        assert_eq!(ledger.record_hash(5, 2, 9), Ok(()));
+        assert_eq!(ledger.record_hash(5, 1, 8), Err(NetError::Desync { tick: 5 }));
+    }
+
+    #[test]
+    fn inputs_are_taken_once_every_snake_sent_one() {
+        let mut ledger = Ledger::new(PLAYERS);
+        ledger.record_input(0, 1, Direction::Up);
+        assert_eq!(ledger.take_inputs(0), None);
+        ledger.record_input(0, 0, Direction::Left);
+        assert_eq!(ledger.take_inputs(0), Some(vec![Direction::Left, Direction::Up]));
+        assert_eq!(ledger.take_inputs(0), None);
+    }
+
+    #[test]
+    fn a_handshake_during_the_game_is_refused() {
+        let (mut host, _guest) = pair();
+        assert!(matches!(host.handle(Message::hello()), Err(NetError::Protocol(_))));
+    }
+
+    #[test]
+    fn a_peer_that_leaves_is_reported() {
+        let (mut host, guest) = pair();
+        drop(guest);
+        let mut result = Ok(None);
+        for _ in 0..1000 {
+            result = host.exchange(7, |heading| heading);
+            if result.is_err() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(matches!(result, Err(NetError::Disconnected | NetError::Io(_))), "{:?}", result);
+    }
+}
@@ -0,0 +1,235 @@
+use std::io::ErrorKind;
+use std::net::TcpListener;
+use bevy::prelude::*;
+
+use crate::board::BoardView;
+use crate::controls::{DirectionQueue, Player, ARROWS};
+use crate::level::{Level, Levels};
+use crate::replay::{Recorder, Replay};
+use crate::rules::GameRules;
+use crate::scoring::Score;
+use crate::settings::Settings;
+use crate::sim::SnakeSim;
+use crate::{start_round, GameState, Notice};
+use lockstep::{Connection, Lockstep, Message, NetError, DEFAULT_PORT, PLAYERS};
+
+pub mod lockstep;
+pub mod server;
+
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<NetRequest>()
+            .init_resource::<NetConfig>()
+            .add_systems(Update, handle_net_requests.run_if(on_event::<NetRequest>()))
+            .add_systems(Update, run_lobby.run_if(resource_exists::<Lobby>()))
+            .add_systems(Update, leave_on_escape.run_if(in_state(GameState::Playing).and_then(resource_exists::<Lockstep>())))
+            .add_systems(OnEnter(GameState::Starting), start_net_round.run_if(resource_exists::<Lockstep>()))
+            .add_systems(FixedUpdate, exchange_inputs
+                .before(crate::tick)
+                .run_if(in_state(GameState::Playing).and_then(resource_exists::<Lockstep>())))
+            .add_systems(OnEnter(GameState::Starting), close_lobby)
+            .add_systems(OnEnter(GameState::Replay), close_lobby)
+            .add_systems(OnExit(GameState::GameOver), end_session)
+            .add_systems(OnEnter(GameState::Menu), end_session);
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct NetConfig {
+    // the port to host on and the host to join
+    pub port: u16,
+    pub address: String,
+}
+
+impl Default for NetConfig {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT,
+            address: format!("127.0.0.1:{}", DEFAULT_PORT),
+        }
+    }
+}
+
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetRequest {
+    Host,
+    Join,
+}
+
+// a networked game being set up: the host listens until the guest connects, then both say hello
+#[derive(Resource)]
+struct Lobby {
+    listener: Option<TcpListener>,
+    connection: Option<Connection>,
+}
+
+// asking for either while a game is being set up calls it off
+fn handle_net_requests(
+    mut commands: Commands,
+    mut requests: EventReader<NetRequest>,
+    config: Res<NetConfig>,
+    lobby: Option<Res<Lobby>>,
+    mut notices: EventWriter<Notice>,
+) {
+    let Some(request) = requests.iter().last() else {
+        return;
+    };
+    if lobby.is_some() {
+        commands.remove_resource::<Lobby>();
+        notices.send(Notice("Stopped waiting for the other player".to_string()));
+        return;
+    }
+
+    let lobby = match request {
+        NetRequest::Host => lockstep::listen(config.port).map(|listener| {
+            (Lobby { listener: Some(listener), connection: None }, format!("Hosting on port {}, waiting for the other player", config.port))
+        }),
+        NetRequest::Join => lockstep::connect(&config.address).map(|mut connection| {
+            connection.send(&Message::hello());
+            (Lobby { listener: None, connection: Some(connection) }, format!("Joined {}, waiting for the host", config.address))
+        }),
+    };
+    match lobby {
+        Ok((lobby, message)) => {
+            commands.insert_resource(lobby);
+            notices.send(Notice(message));
+        }
+        Err(err) => {
+            warn!("{}", err);
+            notices.send(Notice(err.to_string()));
+        }
+    }
+}
+
+fn run_lobby(
+    mut commands: Commands,
+    mut lobby: ResMut<Lobby>,
+    settings: Res<Settings>,
+    levels: Res<Levels>,
+    level_assets: Res<Assets<Level>>,
+    rules: Res<GameRules>,
+    mut notices: EventWriter<Notice>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let round = || (levels.current(&settings, &level_assets), rules.with_settings(&settings));
+    match advance_lobby(&mut lobby, round) {
+        Ok(None) => {}
+        Ok(Some(lockstep)) => {
+            commands.remove_resource::<Lobby>();
+            commands.insert_resource(lockstep);
+            next_state.set(GameState::Starting);
+        }
+        Err(err) => {
+            warn!("{}", err);
+            notices.send(Notice(err.to_string()));
+            commands.remove_resource::<Lobby>();
+        }
+    }
+}
+
+// the host answers the guest's hello with the round to play, taken from its own settings; a guest plays the snake
+// the start names, which lets a dedicated server seat it first
+fn advance_lobby(lobby: &mut Lobby, round: impl FnOnce() -> (Level, GameRules)) -> Result<Option<Lockstep>, NetError> {
+    let host = lobby.listener.is_some();
+    if let (None, Some(listener)) = (&lobby.connection, &lobby.listener) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let mut connection = Connection::new(stream)?;
+                connection.send(&Message::hello());
+                lobby.connection = Some(connection);
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    let Some(connection) = lobby.connection.as_mut() else {
+        return Ok(None);
+    };
+
+    let mut messages = connection.poll()?.into_iter();
+    let mut lockstep = loop {
+        match messages.next() {
+            None => return Ok(None),
+            Some(Message::Hello { version }) if version != crate::replay::file::GAME_VERSION => {
+                return Err(NetError::Version { peer: version });
+            }
+            Some(Message::Hello { .. }) if host => {
+                let (level, rules) = round();
+                let seed = rand::random();
+                connection.send(&Message::Start { seed, level: level.clone(), rules: rules.clone(), player: 1 });
+                break Lockstep::new(lobby.connection.take().unwrap(), 0, seed, level, rules);
+            }
+            Some(Message::Start { seed, level, rules, player }) if !host && player < PLAYERS => {
+                level.check().map_err(|err| NetError::Protocol(err.to_string()))?;
+                rules.check().map_err(|err| NetError::Protocol(err.to_string()))?;
+                break Lockstep::new(lobby.connection.take().unwrap(), player, seed, level, rules);
+            }
+            Some(Message::Hello { .. }) => {}
+            Some(_) => return Err(NetError::Protocol("a game message before the game started".to_string())),
+        }
+    };
+    // the host may already have sent its first inputs along with the round
+    for message in messages {
+        lockstep.handle(message)?;
+    }
+    Ok(Some(lockstep))
+}
+
+fn start_net_round(
+    mut commands: Commands,
+    mut board: BoardView,
+    query_players: Query<Entity, With<Player>>,
+    mut sim: ResMut<SnakeSim>,
+    mut score: ResMut<Score>,
+    mut fixed_time: ResMut<FixedTime>,
+    mut recorder: ResMut<Recorder>,
+    mut lockstep: ResMut<Lockstep>,
+) {
+    let round = SnakeSim::new(lockstep.level.clone(), lockstep.rules.clone(), PLAYERS);
+    let entities = start_round(&mut commands, &mut board, &query_players, &mut fixed_time, &mut lockstep.rng, &mut sim, round);
+    // the other snake is moved by what arrives over the network
+    commands.entity(entities[lockstep.local]).insert(ARROWS);
+
+    *score = Score::new(PLAYERS);
+    recorder.replay = Some(Replay::new(lockstep.seed, &sim));
+}
+
+fn exchange_inputs(
+    mut lockstep: ResMut<Lockstep>,
+    sim: Res<SnakeSim>,
+    mut players: Query<(&Player, &mut DirectionQueue)>,
+    mut notices: EventWriter<Notice>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let local = lockstep.local;
+    let mut queue = players.iter_mut()
+        .find(|(player, _)| player.index == local)
+        .map(|(_, queue)| queue);
+    let next_input = |heading| queue.as_mut().map_or(heading, |queue| queue.next(heading));
+    match lockstep.exchange(sim.state_hash(), next_input) {
+        Ok(inputs) => lockstep.ready = inputs,
+        Err(err) => {
+            warn!("{}", err);
+            notices.send(Notice(format!("Network game ended: {}", err)));
+            next_state.set(GameState::Menu);
+        }
+    }
+}
+
+// a networked game can't be paused, Esc leaves it instead
+fn leave_on_escape(keys: Res<Input<KeyCode>>, mut next_state: ResMut<NextState<GameState>>) {
+    if keys.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::Menu);
+    }
+}
+
+// starting any other game stops waiting for the other player
+fn close_lobby(mut commands: Commands) {
+    commands.remove_resource::<Lobby>();
+}
+
+fn end_session(mut commands: Commands) {
+    commands.remove_resource::<Lockstep>();
+}
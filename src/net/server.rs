@@ -0,0 +1,205 @@
+use std::io::ErrorKind;
+use std::net::TcpListener;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+use crate::level::Level;
+use crate::rng::GameRng;
+use crate::rules::GameRules;
+use crate::scoring::Score;
+use crate::sim::{SimEvent, SnakeSim};
+use super::lockstep::{self, Connection, Ledger, Message, NetError, PLAYERS};
+
+// runs every room's game next to its two players, passing their inputs on and checking their state hashes
+pub struct ServerPlugin;
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, open_server)
+            .add_systems(Update, (accept_clients, greet_clients, run_rooms).chain().run_if(resource_exists::<Server>()));
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct ServerConfig {
+    pub port: u16,
+    // every room plays this round
+    pub level: Level,
+    pub rules: GameRules,
+}
+
+#[derive(Resource)]
+struct Server {
+    listener: TcpListener,
+    // connected but not said hello yet, and said hello but not seated in a room yet
+    greeting: Vec<Connection>,
+    waiting: Vec<Connection>,
+    rooms: Vec<Room>,
+    next_room: u64,
+}
+
+fn open_server(mut commands: Commands, config: Res<ServerConfig>, mut exit: EventWriter<AppExit>) {
+    match lockstep::listen(config.port) {
+        Ok(listener) => {
+            info!("listening on port {}", config.port);
+            commands.insert_resource(Server { listener, greeting: vec![], waiting: vec![], rooms: vec![], next_room: 1 });
+        }
+        Err(err) => {
+            error!("can't listen on port {}: {}", config.port, err);
+            exit.send(AppExit);
+        }
+    }
+}
+
+fn accept_clients(mut server: ResMut<Server>) {
+    loop {
+        match server.listener.accept() {
+            Ok((stream, address)) => match Connection::new(stream) {
+                Ok(mut connection) => {
+                    info!("{} connected", address);
+                    connection.send(&Message::hello());
+                    server.greeting.push(connection);
+                }
+                Err(err) => warn!("{}: {}", address, err),
+            },
+            Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+            Err(err) => {
+                warn!("{}", err);
+                break;
+            }
+        }
+    }
+}
+
+// clients are seated in the order they said hello, two to a room
+fn greet_clients(mut server: ResMut<Server>, config: Res<ServerConfig>) {
+    let server = &mut *server;
+    let mut greeting = vec![];
+    for mut connection in server.greeting.drain(..) {
+        match connection.poll() {
+            Ok(messages) => match messages.first() {
+                None => greeting.push(connection),
+                Some(Message::Hello { version }) if version == crate::replay::file::GAME_VERSION => {
+                    server.waiting.push(connection);
+                }
+                Some(Message::Hello { version }) => warn!("{}", NetError::Version { peer: version.clone() }),
+                Some(_) => warn!("a client skipped saying hello"),
+            },
+            Err(err) => warn!("a client left before saying hello: {}", err),
+        }
+    }
+    server.greeting = greeting;
+
+    while server.waiting.len() >= PLAYERS {
+        let clients = server.waiting.drain(..PLAYERS).map(Some).collect::<Vec<_>>();
+        let room = Room::new(server.next_room, clients, config.level.clone(), config.rules.clone());
+        info!("room {} started with seed {}", room.id, room.seed);
+        server.rooms.push(room);
+        server.next_room += 1;
+    }
+}
+
+fn run_rooms(mut server: ResMut<Server>) {
+    server.rooms.retain_mut(|room| {
+        let over = room.sim.game_over.is_some();
+        match room.update() {
+            Ok(()) if !over && room.sim.game_over.is_some() => {
+                info!("room {}: {}", room.id, room.result());
+                true
+            }
+            // a finished room stays open until both players left, one may still be catching up
+            Ok(()) => room.clients.iter().any(Option::is_some),
+            Err(err) => {
+                warn!("room {} stopped after {} ticks: {}", room.id, room.tick, err);
+                false
+            }
+        }
+    });
+}
+
+// the server's own copy of a game, played with the same inputs and the same sim as both players
+struct Room {
+    id: u64,
+    seed: u64,
+    clients: Vec<Option<Connection>>,
+    sim: SnakeSim,
+    rng: GameRng,
+    score: Score,
+    // the next tick to play
+    tick: u64,
+    // both players' hashes and then the server's
+    ledger: Ledger,
+}
+
+impl Room {
+    fn new(id: u64, mut clients: Vec<Option<Connection>>, level: Level, rules: GameRules) -> Self {
+        let seed = rand::random();
+        for (player, client) in clients.iter_mut().enumerate() {
+            if let Some(client) = client {
+                client.send(&Message::Start { seed, level: level.clone(), rules: rules.clone(), player });
+            }
+        }
+        let mut rng = GameRng::from_seed(seed);
+        let mut sim = SnakeSim::new(level, rules, PLAYERS);
+        sim.reset(&mut rng);
+        Self {
+            id,
+            seed,
+            clients,
+            sim,
+            rng,
+            score: Score::new(PLAYERS),
+            tick: 0,
+            ledger: Ledger::new(PLAYERS + 1),
+        }
+    }
+
+    fn update(&mut self) -> Result<(), NetError> {
+        for player in 0..PLAYERS {
+            let Some(client) = self.clients[player].as_mut() else {
+                continue;
+            };
+            let messages = match client.poll() {
+                Ok(messages) => messages,
+                Err(NetError::Disconnected) if self.sim.game_over.is_some() => {
+                    self.clients[player] = None;
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            for message in messages {
+                self.ledger.handle(player, message.clone())?;
+                // the other player is sent everything as is, so it still checks hashes against its peer
+                if let Some(other) = self.clients[1 - player].as_mut() {
+                    other.send(&message);
+                }
+            }
+        }
+
+        while self.sim.game_over.is_none() {
+            let Some(inputs) = self.ledger.take_inputs(self.tick) else {
+                break;
+            };
+            self.ledger.record_hash(self.tick, PLAYERS, self.sim.state_hash())?;
+            for event in self.sim.step(&inputs, &mut self.rng) {
+                if let SimEvent::FoodEaten { player, points, .. } = event {
+                    self.score.add(player, points);
+                }
+            }
+            self.tick += 1;
+        }
+        Ok(())
+    }
+
+
+    fn result(&self) -> String {
+        let scores = (0..PLAYERS)
+            .map(|player| self.score.of(player).to_string())
+            .collect::<Vec<_>>()
+            .join(" | ");
+        match self.sim.game_over {
+            Some(reason) => format!("{:?} after {} ticks, scores {}", reason, self.tick, scores),
+            None => format!("still playing after {} ticks, scores {}", self.tick, scores),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash, Deserialize, Serialize)]
+pub enum PowerUp {
+    Invincible,
+    SlowMotion,
+    Ghost,
+    ScoreMultiplier,
+    Magnet,
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 5] = [
+        PowerUp::Invincible,
+        PowerUp::SlowMotion,
+        PowerUp::Ghost,
+        PowerUp::ScoreMultiplier,
+        PowerUp::Magnet,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PowerUp::Invincible => "Invincible",
+            PowerUp::SlowMotion => "Slow",
+            PowerUp::Ghost => "Ghost",
+            PowerUp::ScoreMultiplier => "Multiplier",
+            PowerUp::Magnet => "Magnet",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PowerUpRules {
+    // how many times longer a tick takes while slow-motion is active
+    pub slow_motion: f32,
+    pub score_multiplier: u32,
+    // food within this many cells of the head drifts towards it
+    pub magnet_radius: u8,
+}
+
+impl Default for PowerUpRules {
+    fn default() -> Self {
+        Self {
+            slow_motion: 2.0,
+            score_multiplier: 2,
+            magnet_radius: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PowerUps {
+    active: Vec<(PowerUp, u32)>,
+}
+
+impl PowerUps {
+    pub fn activate(&mut self, power_up: PowerUp, ticks: u32) {
+        match self.active.iter_mut().find(|(active, _)| *active == power_up) {
+            Some((_, remaining)) => *remaining = (*remaining).max(ticks),
+            None if ticks > 0 => self.active.push((power_up, ticks)),
+            None => {}
+        }
+    }
+
+    pub fn is_active(&self, power_up: PowerUp) -> bool {
+        self.remaining(power_up).is_some()
+    }
+
+    pub fn remaining(&self, power_up: PowerUp) -> Option<u32> {
+        self.active.iter()
+            .find(|(active, _)| *active == power_up)
+            .map(|(_, remaining)| *remaining)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (PowerUp, u32)> + '_ {
+        self.active.iter().copied()
+    }
+
+    // counts every active power-up down by one tick and returns the ones that ran out
+    pub fn tick(&mut self) -> Vec<PowerUp> {
+        let mut expired = vec![];
+        self.active.retain_mut(|(power_up, remaining)| {
+            *remaining -= 1;
+            if *remaining == 0 {
+                expired.push(*power_up);
+            }
+            *remaining > 0
+        });
+        expired
+    }
+
+    pub fn clear(&mut self) {
+        self.active.clear();
+    }
+}
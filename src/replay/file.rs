@@ -0,0 +1,369 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::board::Position;
+use crate::level::Level;
+use crate::rules::GameRules;
+use crate::settings::MAX_SNAKES;
+use crate::sim::EdgePolicy;
+use crate::snake::Direction;
+use super::Replay;
+
+const MAGIC: &[u8; 4] = b"SNKR";
+pub const FORMAT_VERSION: u16 = 1;
+pub const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const REPLAY_DIR: &str = "replays";
+const EXTENSION: &str = "replay";
+
+// four directions of two bits each fit in a byte
+const DIRECTIONS_PER_BYTE: usize = 4;
+// weeks of play at the fastest speed, anything longer comes from a damaged run length
+const MAX_TICKS: u64 = 1 << 24;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    Io(String),
+    NotFound,
+    NotAReplay,
+    Format { version: u16 },
+    Rules { game_version: String },
+    Corrupt,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "could not access replay file: {}", err),
+            ReplayError::NotFound => write!(f, "no saved replays in `{}`", REPLAY_DIR),
+            ReplayError::NotAReplay => write!(f, "file is not a snake replay"),
+            ReplayError::Format { version } if *version < FORMAT_VERSION =>
+                write!(f, "replay uses the older format {}, this game only reads format {}", version, FORMAT_VERSION),
+            ReplayError::Format { version } =>
+                write!(f, "replay uses format {} from a newer game, this game only reads format {}", version, FORMAT_VERSION),
+            ReplayError::Rules { game_version } =>
+                write!(f, "replay was recorded by snake {} under different rules than the ones loaded now", game_version),
+            ReplayError::Corrupt => write!(f, "replay file is truncated or damaged"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(err: std::io::Error) -> Self {
+        ReplayError::Io(err.to_string())
+    }
+}
+
+// header: magic, format version, game version, rules hash, seed, the settings folded into the rules, the number of
+// snakes and of opponents among them and the level;
+// body: runs of identical ticks, each a length followed by every snake's direction packed two bits apiece
+pub fn encode(replay: &Replay) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(FORMAT_VERSION.to_le_bytes());
+    bytes.push(GAME_VERSION.len() as u8);
+    bytes.extend(GAME_VERSION.as_bytes());
+    bytes.extend(replay.rules.hash().to_le_bytes());
+    bytes.extend(replay.seed.to_le_bytes());
+    bytes.push(match replay.rules.edge_policy {
+        EdgePolicy::Solid => 0,
+        EdgePolicy::Wrap => 1,
+    });
+    bytes.push(replay.rules.food.count);
+    bytes.push(replay.players as u8);
+    bytes.push(replay.opponents as u8);
+
+    let level = &replay.level;
+    bytes.extend([level.width, level.height]);
+    match level.start {
+        Some(start) => bytes.extend([1, start.x, start.y]),
+        None => bytes.push(0),
+    }
+    let mut walls = level.walls.iter().map(|wall| (wall.y, wall.x)).collect::<Vec<_>>();
+    walls.sort();
+    put_varint(&mut bytes, walls.len() as u64);
+    for (y, x) in walls {
+        bytes.extend([x, y]);
+    }
+
+    let runs = replay.inputs.iter().fold(Vec::<(u64, &Vec<Direction>)>::new(), |mut runs, inputs| {
+        match runs.last_mut() {
+            Some((length, last)) if *last == inputs => *length += 1,
+            _ => runs.push((1, inputs)),
+        }
+        runs
+    });
+    put_varint(&mut bytes, runs.len() as u64);
+    for (length, inputs) in runs {
+        put_varint(&mut bytes, length);
+        bytes.extend(pack(inputs, replay.players));
+    }
+    bytes
+}
+
+// the rules aren't stored, only checked: the loaded rules with the recorded settings must hash the same
+pub fn decode(bytes: &[u8], rules: &GameRules) -> Result<Replay, ReplayError> {
+    let mut reader = Reader { bytes };
+    if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+        return Err(ReplayError::NotAReplay);
+    }
+    let version = u16::from_le_bytes(reader.array()?);
+    if version != FORMAT_VERSION {
+        return Err(ReplayError::Format { version });
+    }
+    let length = reader.byte()? as usize;
+    let game_version = String::from_utf8_lossy(reader.take(length)?).into_owned();
+    let rules_hash = u64::from_le_bytes(reader.array()?);
+    let seed = u64::from_le_bytes(reader.array()?);
+
+    let mut rules = rules.clone();
+    rules.edge_policy = match reader.byte()? {
+        0 => EdgePolicy::Solid,
+        1 => EdgePolicy::Wrap,
+        _ => return Err(ReplayError::Corrupt),
+    };
+    rules.food.count = reader.byte()?;
+    if rules.hash() != rules_hash {
+        return Err(ReplayError::Rules { game_version });
+    }
+    let players = reader.byte()? as usize;
+    if !(1..=MAX_SNAKES).contains(&players) {
+        return Err(ReplayError::Corrupt);
+    }
+    let opponents = reader.byte()? as usize;
+    if opponents >= players {
+        return Err(ReplayError::Corrupt);
+    }
+
+    let [width, height] = reader.array()?;
+    let start = match reader.byte()? {
+        0 => None,
+        _ => {
+            let [x, y] = reader.array()?;
+            Some(Position { x, y })
+        }
+    };
+    let walls = (0..reader.varint()?)
+        .map(|_| reader.array().map(|[x, y]| Position { x, y }))
+        .collect::<Result<HashSet<_>, _>>()?;
+    let level = Level { width, height, walls, start };
+    level.check().map_err(|_| ReplayError::Corrupt)?;
+
+    // the runs are only spread out into ticks once the whole file has been read, a few damaged bytes can claim
+    // millions of them
+    let (mut runs, mut ticks) = (vec![], 0);
+    for _ in 0..reader.varint()? {
+        let length = reader.varint()?;
+        ticks += length;
+        if ticks > MAX_TICKS {
+            return Err(ReplayError::Corrupt);
+        }
+        runs.push((length, unpack(reader.take(players.div_ceil(DIRECTIONS_PER_BYTE))?, players)));
+    }
+    if !reader.bytes.is_empty() {
+        return Err(ReplayError::Corrupt);
+    }
+    let inputs = runs.into_iter()
+        .flat_map(|(length, tick)| std::iter::repeat_n(tick, length as usize))
+        .collect();
+
+    Ok(Replay { seed, level, rules, players, opponents, inputs })
+}
+
+pub fn save(replay: &Replay) -> Result<PathBuf, ReplayError> {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = Path::new(REPLAY_DIR).join(format!("{}-{}.{}", seconds, replay.seed, EXTENSION));
+    std::fs::create_dir_all(REPLAY_DIR)?;
+    std::fs::write(&path, encode(replay))?;
+    Ok(path)
+}
+
+pub fn load(path: &Path, rules: &GameRules) -> Result<Replay, ReplayError> {
+    decode(&std::fs::read(path)?, rules)
+}
+
+// the replay saved last, for the menu entry that has no file picker
+pub fn latest() -> Result<PathBuf, ReplayError> {
+    let entries = match std::fs::read_dir(REPLAY_DIR) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Err(ReplayError::NotFound),
+        Err(err) => return Err(err.into()),
+    };
+    entries.filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == EXTENSION))
+        .max_by_key(|path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+        .ok_or(ReplayError::NotFound)
+}
+
+fn put_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn pack(inputs: &[Direction], players: usize) -> Vec<u8> {
+    let mut bytes = vec![0; players.div_ceil(DIRECTIONS_PER_BYTE)];
+    for (player, direction) in inputs.iter().enumerate() {
+        let bits = Direction::ALL.iter().position(|candidate| candidate == direction).unwrap_or_default() as u8;
+        bytes[player / DIRECTIONS_PER_BYTE] |= bits << (player % DIRECTIONS_PER_BYTE * 2);
+    }
+    bytes
+}
+
+fn unpack(bytes: &[u8], players: usize) -> Vec<Direction> {
+    (0..players)
+        .map(|player| Direction::ALL[(bytes[player / DIRECTIONS_PER_BYTE] >> (player % DIRECTIONS_PER_BYTE * 2) & 0b11) as usize])
+        .collect()
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], ReplayError> {
+        if self.bytes.len() < count {
+            return Err(ReplayError::Corrupt);
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ReplayError> {
+        Ok(self.take(N)?.try_into().expect("took exactly N bytes"))
+    }
+
+    fn byte(&mut self) -> Result<u8, ReplayError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, ReplayError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ReplayError::Corrupt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay() -> Replay {
+        let mut level = Level::open(12, 9);
+        level.walls.extend([Position { x: 3, y: 4 }, Position { x: 0, y: 0 }]);
+        level.start = Some(Position { x: 2, y: 2 });
+        let mut rules = GameRules { edge_policy: EdgePolicy::Wrap, ..GameRules::default() };
+        rules.food.count = 3;
+
+        let mut inputs = vec![];
+        inputs.extend(std::iter::repeat_n(vec![Direction::Right, Direction::Left, Direction::Up], 500));
+        inputs.push(vec![Direction::Up, Direction::Left, Direction::Up]);
+        inputs.extend(std::iter::repeat_n(vec![Direction::Down, Direction::Down, Direction::Right], 40));
+        Replay { seed: 0xdead_beef, level, rules, players: 3, opponents: 1, inputs }
+    }
+
+    #[test]
+    fn round_trips_every_tick() {
+        let replay = replay();
+        let decoded = decode(&encode(&replay), &GameRules::default()).unwrap();
+        assert_eq!(decoded, replay);
+    }
+
+    #[test]
+    fn identical_ticks_are_stored_once() {
+        let replay = replay();
+        let mut longer = replay.clone();
+        longer.inputs.extend(std::iter::repeat_n(longer.inputs.last().unwrap().clone(), 10_000));
+        // only the last run length grows, from one varint byte to two
+        assert_eq!(encode(&longer).len(), encode(&replay).len() + 1);
+        assert_eq!(decode(&encode(&longer), &GameRules::default()).unwrap().ticks(), 10_541);
+    }
+
+    #[test]
+    fn an_empty_game_round_trips() {
+        let replay = Replay { inputs: vec![], players: 1, opponents: 0, ..replay() };
+        assert_eq!(decode(&encode(&replay), &GameRules::default()).unwrap(), replay);
+    }
+
+    #[test]
+    fn other_files_are_not_replays() {
+        assert_eq!(decode(b"PNG\x89 not a replay", &GameRules::default()), Err(ReplayError::NotAReplay));
+        assert_eq!(decode(b"", &GameRules::default()), Err(ReplayError::NotAReplay));
+    }
+
+    #[test]
+    fn other_format_versions_are_refused() {
+        let mut bytes = encode(&replay());
+        bytes[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert_eq!(decode(&bytes, &GameRules::default()), Err(ReplayError::Format { version: FORMAT_VERSION + 1 }));
+        bytes[4..6].copy_from_slice(&0u16.to_le_bytes());
+        assert_eq!(decode(&bytes, &GameRules::default()), Err(ReplayError::Format { version: 0 }));
+    }
+
+    #[test]
+    fn different_rules_are_refused() {
+        let mut rules = GameRules::default();
+        rules.speed.start_millis += 1;
+        assert_eq!(
+            decode(&encode(&replay()), &rules),
+            Err(ReplayError::Rules { game_version: GAME_VERSION.to_string() }),
+        );
+    }
+
+    #[test]
+    fn damaged_files_are_corrupt() {
+        let bytes = encode(&replay());
+        for length in [10, bytes.len() / 2, bytes.len() - 1] {
+            assert_eq!(decode(&bytes[..length], &GameRules::default()), Err(ReplayError::Corrupt), "cut at {}", length);
+        }
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert_eq!(decode(&longer, &GameRules::default()), Err(ReplayError::Corrupt));
+    }
+
+    #[test]
+    fn endless_run_lengths_are_corrupt() {
+        let mut replay = replay();
+        replay.inputs.truncate(1);
+        let mut bytes = encode(&replay);
+        // the only run's length is the varint right before the last packed byte
+        let run = bytes.len() - 2;
+        assert_eq!(bytes[run], 1);
+        bytes.splice(run..=run, [0xff, 0xff, 0xff, 0xff, 0x0f]);
+        assert_eq!(decode(&bytes, &GameRules::default()), Err(ReplayError::Corrupt));
+    }
+
+    #[test]
+    fn a_long_run_in_a_cut_off_file_is_corrupt() {
+        let mut replay = replay();
+        replay.inputs.truncate(1);
+        let mut bytes = encode(&replay);
+        // one run as long as a replay may be, with its packed directions missing
+        let run = bytes.len() - 2;
+        bytes.splice(run.., [0x80, 0x80, 0x80, 0x08]);
+        assert_eq!(decode(&bytes, &GameRules::default()), Err(ReplayError::Corrupt));
+    }
+
+    #[test]
+    fn levels_without_room_for_the_snakes_are_corrupt() {
+        let mut replay = replay();
+        replay.level = Level::open(0, 9);
+        assert_eq!(decode(&encode(&replay), &GameRules::default()), Err(ReplayError::Corrupt));
+        replay.level = Level { start: Some(Position { x: 12, y: 0 }), ..Level::open(12, 9) };
+        assert_eq!(decode(&encode(&replay), &GameRules::default()), Err(ReplayError::Corrupt));
+    }
+}
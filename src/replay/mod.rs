@@ -0,0 +1,256 @@
+use std::path::PathBuf;
+use bevy::prelude::*;
+
+use crate::board::BoardView;
+use crate::controls::Player;
+use crate::level::Level;
+use crate::rng::GameRng;
+use crate::rules::GameRules;
+use crate::sim::{SimEvent, SnakeSim};
+use crate::snake::Direction;
+use crate::{GameState, Notice};
+
+pub mod file;
+
+pub const MIN_PLAYBACK_SPEED: f32 = 0.5;
+pub const MAX_PLAYBACK_SPEED: f32 = 8.0;
+const SEEK_TICKS: usize = 25;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ReplayRequest>()
+            .add_systems(Update, handle_replay_requests.run_if(on_event::<ReplayRequest>()))
+            .add_systems(OnEnter(GameState::Replay), start_playback)
+            .add_systems(OnExit(GameState::Replay), stop_playback)
+            .add_systems(Update, playback_controls.run_if(in_state(GameState::Replay)))
+            .add_systems(FixedUpdate, playback_tick.run_if(in_state(GameState::Replay)));
+    }
+}
+
+// everything needed to play a game again: how it started and what every snake was told to do on each tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub level: Level,
+    pub rules: GameRules,
+    pub players: usize,
+    pub opponents: usize,
+    pub inputs: Vec<Vec<Direction>>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, sim: &SnakeSim) -> Self {
+        Self {
+            seed,
+            level: sim.level.clone(),
+            rules: sim.rules.clone(),
+            players: sim.snakes.len(),
+            opponents: sim.opponents,
+            inputs: vec![],
+        }
+    }
+
+    pub fn ticks(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn start(&self) -> (SnakeSim, GameRng) {
+        let mut sim = SnakeSim::new(self.level.clone(), self.rules.clone(), self.players).with_opponents(self.opponents);
+        let mut rng = GameRng::from_seed(self.seed);
+        sim.reset(&mut rng);
+        (sim, rng)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Recorder {
+    pub replay: Option<Replay>,
+}
+
+impl Recorder {
+    pub fn record(&mut self, inputs: &[Direction]) {
+        if let Some(replay) = self.replay.as_mut() {
+            replay.inputs.push(inputs.to_vec());
+        }
+    }
+}
+
+#[derive(Event, Debug, Clone)]
+pub enum ReplayRequest {
+    Save,
+    // a replay file to watch, or the one saved last
+    Load(Option<PathBuf>),
+}
+
+#[derive(Resource)]
+pub struct Playback {
+    pub replay: Replay,
+    rng: GameRng,
+    pub tick: usize,
+    pub paused: bool,
+    pub speed: f32,
+}
+
+impl Playback {
+    // the playback together with the sim it steps, both at the first tick
+    pub fn new(replay: Replay) -> (Self, SnakeSim) {
+        let (sim, rng) = replay.start();
+        (Self { replay, rng, tick: 0, paused: false, speed: 1.0 }, sim)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.tick >= self.replay.ticks()
+    }
+
+    pub fn step(&mut self, sim: &mut SnakeSim) -> Vec<SimEvent> {
+        let Some(inputs) = self.replay.inputs.get(self.tick) else {
+            return vec![];
+        };
+        self.tick += 1;
+        sim.step(inputs, &mut self.rng)
+    }
+
+    // going back starts over from the first tick, the sim can't be run backwards
+    fn seek(&mut self, sim: &mut SnakeSim, tick: usize) {
+        let tick = tick.min(self.replay.ticks());
+        if tick < self.tick {
+            (*sim, self.rng) = self.replay.start();
+            self.tick = 0;
+        }
+        while self.tick < tick {
+            self.step(sim);
+        }
+    }
+
+    fn period(&self, sim: &SnakeSim) -> std::time::Duration {
+        sim.period().div_f32(self.speed)
+    }
+}
+
+fn handle_replay_requests(
+    mut requests: EventReader<ReplayRequest>,
+    mut notices: EventWriter<Notice>,
+    mut recorder: ResMut<Recorder>,
+    rules: Res<GameRules>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for request in requests.iter() {
+        let result = match request {
+            ReplayRequest::Save => match recorder.replay.as_ref() {
+                Some(replay) => file::save(replay).map(|path| format!("Saved {}", path.display())),
+                None => Ok("Nothing to save yet".to_string()),
+            },
+            ReplayRequest::Load(path) => path.clone()
+                .map_or_else(file::latest, Ok)
+                .and_then(|path| file::load(&path, &rules).map(|replay| (path, replay)))
+                .map(|(path, replay)| {
+                    recorder.replay = Some(replay);
+                    next_state.set(GameState::Replay);
+                    format!("Playing {}", path.display())
+                }),
+        };
+        match result {
+            Ok(message) => {
+                info!("{}", message);
+                notices.send(Notice(message));
+            }
+            Err(err) => {
+                warn!("{}", err);
+                notices.send(Notice(err.to_string()));
+            }
+        }
+    }
+}
+
+fn start_playback(
+    mut commands: Commands,
+    mut board: BoardView,
+    players: Query<Entity, With<Player>>,
+    mut sim: ResMut<SnakeSim>,
+    mut fixed_time: ResMut<FixedTime>,
+    recorder: Res<Recorder>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(replay) = recorder.replay.clone() else {
+        next_state.set(GameState::Menu);
+        return;
+    };
+    for entity in players.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let playback;
+    (playback, *sim) = Playback::new(replay);
+    board.redraw(&sim);
+    *fixed_time = FixedTime::new(playback.period(&sim));
+    commands.insert_resource(playback);
+}
+
+fn stop_playback(mut commands: Commands) {
+    commands.remove_resource::<Playback>();
+}
+
+fn playback_tick(
+    mut board: BoardView,
+    mut sim: ResMut<SnakeSim>,
+    mut playback: ResMut<Playback>,
+    mut fixed_time: ResMut<FixedTime>,
+) {
+    if playback.paused {
+        return;
+    }
+    let events = playback.step(&mut sim);
+    board.apply(&sim, &events);
+    if events.iter().any(|event| matches!(event, SimEvent::SpeedChanged(_))) {
+        fixed_time.period = playback.period(&sim);
+    }
+    if playback.finished() {
+        playback.paused = true;
+    }
+}
+
+// Space pauses, N steps while paused, Up and Down change the speed, Left and Right seek, Home rewinds, Esc leaves
+fn playback_controls(
+    keys: Res<Input<KeyCode>>,
+    mut board: BoardView,
+    mut sim: ResMut<SnakeSim>,
+    mut playback: ResMut<Playback>,
+    mut fixed_time: ResMut<FixedTime>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let seek_to = if keys.just_pressed(KeyCode::Home) || keys.just_pressed(KeyCode::Space) && playback.finished() {
+        Some(0)
+    } else if keys.just_pressed(KeyCode::Left) {
+        Some(playback.tick.saturating_sub(SEEK_TICKS))
+    } else if keys.just_pressed(KeyCode::Right) {
+        Some(playback.tick + SEEK_TICKS)
+    } else {
+        None
+    };
+    if let Some(tick) = seek_to {
+        playback.seek(&mut sim, tick);
+        board.redraw(&sim);
+    }
+
+    if keys.just_pressed(KeyCode::Space) {
+        // a finished replay was rewound above and starts playing again
+        playback.paused = !playback.paused && seek_to.is_none();
+    }
+    if keys.just_pressed(KeyCode::N) && playback.paused {
+        let events = playback.step(&mut sim);
+        board.apply(&sim, &events);
+    }
+    if keys.just_pressed(KeyCode::Up) {
+        playback.speed = (playback.speed * 2.0).min(MAX_PLAYBACK_SPEED);
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        playback.speed = (playback.speed / 2.0).max(MIN_PLAYBACK_SPEED);
+    }
+    fixed_time.period = playback.period(&sim);
+
+    if keys.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::Menu);
+    }
+}
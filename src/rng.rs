@@ -0,0 +1,59 @@
+use bevy::prelude::Resource;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+#[derive(Resource, Debug, Clone)]
+pub struct GameRng {
+    seed: u64,
+    pinned: bool,
+    rng: StdRng,
+}
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self::new(seed, true)
+    }
+
+    fn new(seed: u64, pinned: bool) -> Self {
+        Self {
+            seed,
+            pinned,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn restart(&mut self) {
+        if !self.pinned {
+            self.seed = rand::random();
+        }
+        self.rng = StdRng::seed_from_u64(self.seed);
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(rand::random(), false)
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
@@ -0,0 +1,245 @@
+use std::fmt;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use bevy::utils::BoxedFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::board::Position;
+use crate::food::{FoodKind, FoodType};
+use crate::powerup::PowerUpRules;
+use crate::settings::{Settings, MAX_FOOD_COUNT};
+use crate::sim::{fnv1a, EdgePolicy};
+use crate::snake::Direction;
+
+#[cfg(not(target_arch = "wasm32"))]
+const RULES_PATH: &str = "rules.ron";
+const EMBEDDED_RULES: &str = include_str!("../assets/rules.ron");
+// slower than this and a slow-motion tick outlasts any sensible game
+pub const MAX_SLOW_MOTION: f32 = 10.0;
+
+pub struct RulesPlugin;
+
+impl Plugin for RulesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<GameRules>()
+            .init_asset_loader::<RulesLoader>()
+            .init_resource::<GameRules>();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems(Startup, load_rules)
+            .add_systems(Update, apply_rules.run_if(on_event::<AssetEvent<GameRules>>()));
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq, Deserialize, Serialize, TypeUuid, TypePath)]
+#[uuid = "0197079f-85b9-480d-a9ad-ef852d045670"]
+pub struct GameRules {
+    pub speed: SpeedRules,
+    pub food: FoodRules,
+    pub start: StartRules,
+    pub edge_policy: EdgePolicy,
+    #[serde(default)]
+    pub power_ups: PowerUpRules,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SpeedRules {
+    pub start_millis: u64,
+    pub min_millis: u64,
+    pub step_millis: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FoodRules {
+    pub count: u8,
+    pub kinds: Vec<FoodKind>,
+}
+
+impl FoodRules {
+    pub fn kind(&self, food_type: FoodType) -> FoodKind {
+        self.kinds.get(food_type.0).cloned().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StartRules {
+    pub length: u8,
+    pub position: Option<Position>,
+    pub direction: Direction,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        ron::de::from_str(EMBEDDED_RULES).expect("embedded rules.ron is valid")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RulesError {
+    NoMinimumTick,
+    StartBelowMinimum { start_millis: u64, min_millis: u64 },
+    SlowMotion(f32),
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::NoMinimumTick => write!(f, "speed.min_millis must be at least 1"),
+            RulesError::StartBelowMinimum { start_millis, min_millis } =>
+                write!(f, "speed.start_millis {} is below speed.min_millis {}", start_millis, min_millis),
+            RulesError::SlowMotion(factor) =>
+                write!(f, "power_ups.slow_motion {} is not between 1 and {}", factor, MAX_SLOW_MOTION),
+        }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+impl GameRules {
+    // a zero tick would make the fixed timestep loop forever, a huge slow-motion factor overflows the tick length
+    pub fn check(&self) -> Result<(), RulesError> {
+        let speed = &self.speed;
+        if speed.min_millis == 0 {
+            return Err(RulesError::NoMinimumTick);
+        }
+        if speed.start_millis < speed.min_millis {
+            let (start_millis, min_millis) = (speed.start_millis, speed.min_millis);
+            return Err(RulesError::StartBelowMinimum { start_millis, min_millis });
+        }
+        let slow_motion = self.power_ups.slow_motion;
+        if !(1.0..=MAX_SLOW_MOTION).contains(&slow_motion) {
+            return Err(RulesError::SlowMotion(slow_motion));
+        }
+        Ok(())
+    }
+
+    // rules that didn't come through the loader are pulled into range rather than trusted
+    pub fn with_settings(&self, settings: &Settings) -> Self {
+        let mut rules = Self {
+            edge_policy: settings.edge_policy.unwrap_or(self.edge_policy),
+            ..self.clone()
+        };
+        rules.food.count = settings.food_count.unwrap_or(rules.food.count).clamp(1, MAX_FOOD_COUNT);
+        rules.speed.min_millis = rules.speed.min_millis.max(1);
+        rules.speed.start_millis = rules.speed.start_millis.max(rules.speed.min_millis);
+        rules.power_ups.slow_motion = match rules.power_ups.slow_motion {
+            factor if factor.is_nan() => 1.0,
+            factor => factor.clamp(1.0, MAX_SLOW_MOTION),
+        };
+        rules
+    }
+
+    pub fn hash(&self) -> u64 {
+        fnv1a(ron::to_string(self).expect("rules serialize to ron").as_bytes())
+    }
+}
+
+#[derive(Default)]
+pub struct RulesLoader;
+
+impl AssetLoader for RulesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let rules = ron::de::from_bytes::<GameRules>(bytes)?;
+            rules.check()?;
+            load_context.set_default_asset(LoadedAsset::new(rules));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Resource)]
+struct RulesHandle(Handle<GameRules>);
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_rules(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(RulesHandle(asset_server.load(RULES_PATH)));
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn apply_rules(
+    mut events: EventReader<AssetEvent<GameRules>>,
+    handle: Res<RulesHandle>,
+    assets: Res<Assets<GameRules>>,
+    mut rules: ResMut<GameRules>,
+    state: Res<State<crate::GameState>>,
+    mut next_state: ResMut<NextState<crate::GameState>>,
+    lockstep: Option<Res<crate::net::lockstep::Lockstep>>,
+) {
+    let changed = events.iter().any(|event| match event {
+        AssetEvent::Created { handle: changed } | AssetEvent::Modified { handle: changed } => changed == &handle.0,
+        AssetEvent::Removed { .. } => false,
+    });
+    let Some(loaded) = assets.get(&handle.0).filter(|_| changed) else {
+        return;
+    };
+
+    // a round that is being played starts over, so its replay and ghost are recorded under the rules it ran on; a
+    // networked game keeps the rules both sides agreed on, and a replay keeps the ones it was recorded with
+    *rules = loaded.clone();
+    if lockstep.is_some() {
+        return;
+    }
+    match state.get() {
+        crate::GameState::Playing | crate::GameState::Paused => next_state.set(crate::GameState::Starting),
+        crate::GameState::Demo => next_state.set(crate::GameState::Menu),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with(change: impl FnOnce(&mut GameRules)) -> Result<(), RulesError> {
+        let mut rules = GameRules::default();
+        change(&mut rules);
+        rules.check()
+    }
+
+    #[test]
+    fn the_bundled_rules_are_valid() {
+        assert_eq!(GameRules::default().check(), Ok(()));
+    }
+
+    #[test]
+    fn a_zero_minimum_tick_is_rejected() {
+        assert_eq!(with(|rules| rules.speed.min_millis = 0), Err(RulesError::NoMinimumTick));
+    }
+
+    #[test]
+    fn a_start_tick_below_the_minimum_is_rejected() {
+        let rejected = with(|rules| rules.speed.start_millis = rules.speed.min_millis - 1);
+        assert_eq!(rejected, Err(RulesError::StartBelowMinimum { start_millis: 99, min_millis: 100 }));
+        let rejected = with(|rules| rules.speed.start_millis = 0);
+        assert_eq!(rejected, Err(RulesError::StartBelowMinimum { start_millis: 0, min_millis: 100 }));
+    }
+
+    #[test]
+    fn slow_motion_must_be_finite_and_in_range() {
+        for factor in [f32::INFINITY, f32::NAN, 1e30, MAX_SLOW_MOTION + 1.0, 0.5, -2.0] {
+            let rejected = with(|rules| rules.power_ups.slow_motion = factor);
+            assert!(matches!(rejected, Err(RulesError::SlowMotion(_))), "{}", factor);
+        }
+        assert_eq!(with(|rules| rules.power_ups.slow_motion = MAX_SLOW_MOTION), Ok(()));
+    }
+
+    #[test]
+    fn settings_pull_unchecked_rules_into_range() {
+        let mut rules = GameRules::default();
+        rules.speed.min_millis = 0;
+        rules.speed.start_millis = 0;
+        rules.power_ups.slow_motion = f32::INFINITY;
+        assert_eq!(rules.with_settings(&Settings::default()).check(), Ok(()));
+    }
+}
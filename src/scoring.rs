@@ -2,29 +2,65 @@ use instant::{Duration, Instant};
 use bevy::app::App;
 use bevy::prelude::*;
 use crate::GameState;
+use crate::replay::{Recorder, Replay};
+use crate::sim::SnakeSim;
 
 #[derive(Resource, Default)]
 pub struct Score {
-    pub score: u32
+    // player one, the only score high scores are kept for
+    pub score: u32,
+    // every other player, starting with player two
+    pub rivals: Vec<u32>,
+    pub new_record: bool,
+}
+
+impl Score {
+    pub fn new(players: usize) -> Self {
+        Self {
+            rivals: vec![0; players.saturating_sub(1)],
+            ..default()
+        }
+    }
+
+    pub fn of(&self, player: usize) -> u32 {
+        match player {
+            0 => self.score,
+            _ => self.rivals.get(player - 1).copied().unwrap_or_default(),
+        }
+    }
+
+    pub fn add(&mut self, player: usize, points: u32) {
+        match player {
+            0 => self.score += points,
+            _ => if let Some(score) = self.rivals.get_mut(player - 1) {
+                *score += points;
+            }
+        }
+    }
 }
 
 #[derive(Resource, Default)]
 pub struct HighScore {
     pub score: u32,
-    pub time: Duration
+    pub time: Duration,
+    // how the record was played, raced against as a ghost
+    pub replay: Option<Replay>,
 }
 
-#[derive(Resource, Debug)]
+#[derive(Resource, Debug, Default)]
 pub struct Timer {
     pub start: Option<Instant>,
     pub runtime: Option<Duration>,
+    pub paused_at: Option<Instant>,
 }
 
-impl Default for Timer {
-    fn default() -> Self {
-        Timer {
-            start: None,
-            runtime: None,
+impl Timer {
+    pub fn elapsed(&self) -> Option<Duration> {
+        match *self {
+            Timer { runtime: Some(runtime), .. } => Some(runtime),
+            Timer { start: Some(start), paused_at: Some(paused_at), .. } => Some(paused_at - start),
+            Timer { start: Some(start), .. } => Some(start.elapsed()),
+            _ => None,
         }
     }
 }
@@ -38,8 +74,10 @@ impl Plugin for ScorePlugin {
         app.init_resource::<Score>()
             .init_resource::<HighScore>()
             .init_resource::<Timer>()
-            .add_systems(OnEnter(GameState::Playing), start_timer)
-            .add_systems(OnExit(GameState::Playing), close_timer);
+            .add_systems(OnEnter(GameState::Starting), start_timer)
+            .add_systems(OnEnter(GameState::Paused), pause_timer)
+            .add_systems(OnTransition { from: GameState::Paused, to: GameState::Playing }, resume_timer)
+            .add_systems(OnTransition { from: GameState::Playing, to: GameState::GameOver }, close_timer);
     }
 }
 
@@ -48,21 +86,40 @@ fn start_timer(mut timer: ResMut<Timer>) {
     *timer = Timer {
         start: Some(Instant::now()),
         runtime: None,
+        paused_at: None,
     };
 }
 
+fn pause_timer(mut timer: ResMut<Timer>) {
+    timer.paused_at = Some(Instant::now());
+}
+
+fn resume_timer(mut timer: ResMut<Timer>) {
+    if let (Some(start), Some(paused_at)) = (timer.start, timer.paused_at.take()) {
+        timer.start = Some(start + paused_at.elapsed());
+    }
+}
+
 fn close_timer(
     mut timer: ResMut<Timer>,
-    score: Res<Score>,
+    mut score: ResMut<Score>,
     mut high_score: ResMut<HighScore>,
+    recorder: Res<Recorder>,
+    sim: Res<SnakeSim>,
 ) {
     let elapsed = timer.start.unwrap().elapsed();
     timer.runtime = Some(elapsed);
+    // computer opponents don't make it a versus game, player one's score still counts
+    if sim.humans() > 1 {
+        return;
+    }
     if score.score > high_score.score || score.score == high_score.score && elapsed < high_score.time
     {
         *high_score = HighScore {
             score: score.score,
             time: elapsed,
-        }
+            replay: recorder.replay.clone(),
+        };
+        score.new_record = true;
     }
 }
@@ -0,0 +1,70 @@
+use bevy::prelude::Resource;
+
+use crate::ai::Difficulty;
+use crate::sim::EdgePolicy;
+
+pub const BOARD_SIZES: [(u8, u8); 4] = [(20, 20), (24, 16), (16, 12), (12, 12)];
+const MIN_BOARD_SIZE: u8 = 5;
+pub const MAX_FOOD_COUNT: u8 = 5;
+pub const MAX_PLAYERS: usize = 2;
+pub const MAX_SNAKES: usize = 4;
+
+#[derive(Resource, Debug, Clone)]
+pub struct Settings {
+    pub edge_policy: Option<EdgePolicy>,
+    pub board_width: u8,
+    pub board_height: u8,
+    pub level: Option<String>,
+    pub food_count: Option<u8>,
+    pub players: usize,
+    pub opponents: usize,
+    pub difficulty: Difficulty,
+    pub autopilot: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let (board_width, board_height) = BOARD_SIZES[0];
+        Self {
+            edge_policy: None,
+            board_width,
+            board_height,
+            level: None,
+            food_count: None,
+            players: 1,
+            opponents: 0,
+            difficulty: Difficulty::default(),
+            autopilot: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn next_board_size(&mut self) {
+        let current = BOARD_SIZES.iter()
+            .position(|size| *size == (self.board_width, self.board_height));
+        let next = current.map_or(0, |index| (index + 1) % BOARD_SIZES.len());
+        (self.board_width, self.board_height) = BOARD_SIZES[next];
+    }
+
+    pub fn next_food_count(&mut self, current: u8) {
+        self.food_count = Some(current % MAX_FOOD_COUNT + 1);
+    }
+
+    pub fn next_opponents(&mut self) {
+        self.opponents = (self.opponents + 1) % MAX_SNAKES;
+    }
+
+    pub fn next_level<'a>(&mut self, mut paths: impl Iterator<Item = &'a str>) {
+        self.level = match &self.level {
+            None => paths.next(),
+            Some(current) => paths.skip_while(|path| path != current).nth(1),
+        }.map(str::to_string);
+    }
+}
+
+pub fn parse_board_size(value: &str) -> Option<(u8, u8)> {
+    let (width, height) = value.split_once('x')?;
+    let (width, height) = (width.trim().parse().ok()?, height.trim().parse().ok()?);
+    (width >= MIN_BOARD_SIZE && height >= MIN_BOARD_SIZE).then_some((width, height))
+}
@@ -0,0 +1,760 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use bevy::prelude::Resource;
+use itertools::Itertools;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::board::Position;
+use crate::food::{FoodEffect, FoodType};
+use crate::level::Level;
+use crate::powerup::{PowerUp, PowerUps};
+use crate::rules::{GameRules, SpeedRules};
+use crate::snake::{Direction, Snake};
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum GameOverReason {
+    HitWall,
+    HitSnake,
+    Win,
+    Winner(usize),
+    Draw,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub enum EdgePolicy {
+    #[default]
+    Solid,
+    Wrap,
+}
+
+impl EdgePolicy {
+    pub fn toggled(self) -> Self {
+        match self {
+            EdgePolicy::Solid => EdgePolicy::Wrap,
+            EdgePolicy::Wrap => EdgePolicy::Solid,
+        }
+    }
+}
+
+impl std::fmt::Display for EdgePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EdgePolicy::Solid => write!(f, "Solid"),
+            EdgePolicy::Wrap => write!(f, "Wrap"),
+        }
+    }
+}
+
+impl std::str::FromStr for EdgePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "solid" => Ok(EdgePolicy::Solid),
+            "wrap" => Ok(EdgePolicy::Wrap),
+            _ => Err(format!("unknown edge policy `{}`", s)),
+        }
+    }
+}
+
+// FNV-1a, stable across builds and machines unlike the std hasher
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SimEvent {
+    SnakeMoved { player: usize, head: Position, tail: Option<Position> },
+    FoodEaten { player: usize, position: Position, food_type: FoodType, points: u32 },
+    SegmentRemoved { player: usize, position: Position },
+    FoodSpawned { position: Position, food_type: FoodType },
+    FoodExpired { position: Position },
+    FoodMoved { from: Position, to: Position },
+    SpeedChanged(Duration),
+    GameOver(GameOverReason),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct PlacedFood {
+    pub position: Position,
+    pub food_type: FoodType,
+    pub expires_in: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Speed {
+    pub current: Duration,
+    pub default_duration: Duration,
+    pub change_step_duration: Duration,
+    pub min_duration: Duration,
+}
+
+impl Speed {
+    pub fn new(rules: &SpeedRules) -> Self {
+        Self {
+            current: Duration::from_millis(rules.start_millis),
+            default_duration: Duration::from_millis(rules.start_millis),
+            change_step_duration: Duration::from_millis(rules.step_millis),
+            min_duration: Duration::from_millis(rules.min_millis),
+        }
+    }
+
+    fn speed_up(&mut self) {
+        if self.current > self.min_duration {
+            self.current = self.current.saturating_sub(self.change_step_duration).max(self.min_duration);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.current = self.default_duration;
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self::new(&GameRules::default().speed)
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct SnakeSim {
+    pub snakes: Vec<Snake>,
+    pub level: Level,
+    pub rules: GameRules,
+    pub food: Vec<PlacedFood>,
+    pub speed: Speed,
+    pub game_over: Option<GameOverReason>,
+    // the last snakes are computer opponents, the game goes on without them when they crash
+    pub opponents: usize,
+}
+
+impl Default for SnakeSim {
+    fn default() -> Self {
+        Self::new(Level::open(20, 20), Default::default(), 1)
+    }
+}
+
+impl SnakeSim {
+    pub fn new(level: Level, rules: GameRules, players: usize) -> Self {
+        let mut sim = Self {
+            snakes: vec![],
+            level,
+            speed: Speed::new(&rules.speed),
+            rules,
+            food: vec![],
+            game_over: None,
+            opponents: 0,
+        };
+        sim.start_snakes(players.max(1));
+        sim
+    }
+
+    pub fn with_opponents(self, opponents: usize) -> Self {
+        Self { opponents: opponents.min(self.snakes.len() - 1), ..self }
+    }
+
+    pub fn humans(&self) -> usize {
+        self.snakes.len() - self.opponents
+    }
+
+    pub fn reset<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Vec<SimEvent> {
+        self.food.clear();
+        self.start_snakes(self.snakes.len());
+        self.speed.reset();
+        self.game_over = None;
+
+        let mut events = vec![];
+        self.spawn_food(FoodType(0), rng, &mut events);
+        self.refill_food(rng, &mut events);
+        events
+    }
+
+    // fingerprint of everything a tick can change, networked peers compare it to catch a desync
+    pub fn state_hash(&self) -> u64 {
+        let mut bytes = vec![];
+        for snake in self.snakes.iter() {
+            bytes.push(Direction::ALL.iter().position(|direction| *direction == snake.heading).unwrap_or_default() as u8);
+            bytes.extend(snake.pending_growth.to_le_bytes());
+            for (power_up, remaining) in snake.power_ups.iter() {
+                bytes.push(PowerUp::ALL.iter().position(|candidate| *candidate == power_up).unwrap_or_default() as u8);
+                bytes.extend(remaining.to_le_bytes());
+            }
+            bytes.extend((snake.segments.len() as u32).to_le_bytes());
+            bytes.extend(snake.segments.iter().flat_map(|segment| [segment.x, segment.y]));
+        }
+        for food in self.food.iter() {
+            bytes.extend([food.position.x, food.position.y]);
+            bytes.extend((food.food_type.0 as u32).to_le_bytes());
+            bytes.extend(food.expires_in.unwrap_or(u32::MAX).to_le_bytes());
+        }
+        bytes.extend((self.speed.current.as_millis() as u64).to_le_bytes());
+        bytes.push(u8::from(self.game_over.is_some()));
+        fnv1a(&bytes)
+    }
+
+    // `inputs` holds one direction per snake, missing entries keep the current heading
+    pub fn step<R: Rng + ?Sized>(&mut self, inputs: &[Direction], rng: &mut R) -> Vec<SimEvent> {
+        let mut events = vec![];
+        if self.game_over.is_some() {
+            return events;
+        }
+
+        let moves = self.snakes.iter()
+            .enumerate()
+            .map(|(player, snake)| {
+                let input = inputs.get(player).copied().unwrap_or(snake.heading);
+                let direction = if input == snake.heading.opposite() { snake.heading } else { input };
+                (direction, snake.segments.front().and_then(|head| self.next_position(*head, direction)))
+            })
+            .collect::<Vec<_>>();
+
+        let crashes = (0..self.snakes.len())
+            .filter(|player| !self.snakes[*player].is_out())
+            .filter_map(|player| self.collision(player, &moves).map(|reason| (player, reason)))
+            .collect::<Vec<_>>();
+        let humans = self.humans();
+        let crashed = |player: usize| crashes.iter().any(|(crashed, _)| *crashed == player);
+        if let Some(&(_, reason)) = crashes.iter().find(|(player, _)| *player < humans) {
+            let reason = if humans == 1 { reason } else { self.decide(|player| player < humans && !crashed(player)) };
+            self.finish(reason, &mut events);
+            return events;
+        }
+
+        for (player, (direction, next_position)) in moves.into_iter().enumerate() {
+            if crashed(player) {
+                self.knock_out(player, &mut events);
+            } else if let Some(next_position) = next_position {
+                self.move_snake(player, direction, next_position, &mut events);
+            }
+        }
+        self.age_food(&mut events);
+        self.pull_food(&mut events);
+
+        let occupied = self.snakes.iter().map(|snake| snake.segments.len()).sum::<usize>();
+        if occupied >= self.level.cell_count() {
+            let reason = if humans == 1 { GameOverReason::Win } else { self.decide(|player| player < humans) };
+            self.finish(reason, &mut events);
+            return events;
+        }
+
+        self.refill_food(rng, &mut events);
+        events
+    }
+
+    pub fn period(&self) -> Duration {
+        if self.snakes.iter().any(|snake| snake.power_ups.is_active(PowerUp::SlowMotion)) {
+            self.speed.current.mul_f32(self.rules.power_ups.slow_motion.max(1.0))
+        } else {
+            self.speed.current
+        }
+    }
+
+    pub fn free_cells(&self) -> Vec<Position> {
+        (0..self.level.width)
+            .cartesian_product(0..self.level.height)
+            .map(|(x, y)| Position { x, y })
+            .filter(|pos| !self.level.walls.contains(pos))
+            .filter(|pos| !self.is_snake(pos))
+            .filter(|pos| !self.food.iter().any(|food| &food.position == pos))
+            .collect()
+    }
+
+    pub fn is_snake(&self, position: &Position) -> bool {
+        self.snakes.iter().any(|snake| snake.segments.contains(position))
+    }
+
+    pub fn next_position(&self, head: Position, direction: Direction) -> Option<Position> {
+        let (dx, dy) = match direction {
+            Direction::Up => (0, 1),
+            Direction::Down => (0, -1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        };
+        let (width, height) = (i16::from(self.level.width), i16::from(self.level.height));
+        let (x, y) = (i16::from(head.x) + dx, i16::from(head.y) + dy);
+
+        match self.rules.edge_policy {
+            EdgePolicy::Solid if x < 0 || y < 0 || x >= width || y >= height => None,
+            EdgePolicy::Solid => Some(Position { x: x as u8, y: y as u8 }),
+            EdgePolicy::Wrap => Some(Position { x: x.rem_euclid(width) as u8, y: y.rem_euclid(height) as u8 }),
+        }
+    }
+
+    fn collision(&self, player: usize, moves: &[(Direction, Option<Position>)]) -> Option<GameOverReason> {
+        let snake = &self.snakes[player];
+        let invincible = snake.power_ups.is_active(PowerUp::Invincible);
+        let ghost = invincible || snake.power_ups.is_active(PowerUp::Ghost);
+        let Some(next_position) = moves[player].1 else {
+            return Some(GameOverReason::HitWall);
+        };
+        if !invincible && self.level.walls.contains(&next_position) {
+            return Some(GameOverReason::HitWall);
+        }
+        if !ghost && snake.segments.contains(&next_position) {
+            return Some(GameOverReason::HitSnake);
+        }
+        // other snakes block even a ghost, two heads meeting on one cell crash both
+        let hits_other = self.snakes.iter()
+            .enumerate()
+            .filter(|(other, _)| *other != player)
+            .any(|(other, snake)| snake.segments.contains(&next_position) || moves[other].1 == Some(next_position));
+        (!invincible && hits_other).then_some(GameOverReason::HitSnake)
+    }
+
+    // versus only: the longest of the players still in the game wins, a tie is a draw
+    fn decide(&self, in_game: impl Fn(usize) -> bool) -> GameOverReason {
+        let lengths = (0..self.snakes.len())
+            .filter(|player| in_game(*player))
+            .map(|player| (player, self.snakes[player].segments.len()))
+            .collect::<Vec<_>>();
+        let longest = lengths.iter().map(|(_, length)| *length).max();
+        match lengths.iter().filter(|(_, length)| Some(*length) == longest).collect::<Vec<_>>()[..] {
+            [(player, _)] => GameOverReason::Winner(*player),
+            _ => GameOverReason::Draw,
+        }
+    }
+
+    fn knock_out(&mut self, player: usize, events: &mut Vec<SimEvent>) {
+        for position in std::mem::take(&mut self.snakes[player].segments) {
+            events.push(SimEvent::SegmentRemoved { player, position });
+        }
+    }
+
+    fn move_snake(&mut self, player: usize, direction: Direction, next_position: Position, events: &mut Vec<SimEvent>) {
+        let snake = &mut self.snakes[player];
+        let expired = snake.power_ups.tick();
+
+        snake.segments.push_front(next_position);
+        snake.heading = direction;
+        let eaten = self.food.iter()
+            .position(|food| food.position == next_position)
+            .map(|index| self.food.remove(index));
+        let snake = &mut self.snakes[player];
+        if let Some(food) = eaten {
+            snake.pending_growth += self.rules.food.kind(food.food_type).growth;
+        }
+        let tail = if snake.pending_growth > 0 {
+            snake.pending_growth -= 1;
+            None
+        } else {
+            snake.segments.pop_back()
+        };
+        events.push(SimEvent::SnakeMoved { player, head: next_position, tail });
+
+        if expired.contains(&PowerUp::SlowMotion) {
+            events.push(SimEvent::SpeedChanged(self.period()));
+        }
+        if let Some(food) = eaten {
+            self.eat(player, food, events);
+        }
+    }
+
+    // placed one after the other, so a snake never starts on one placed before it
+    fn start_snakes(&mut self, players: usize) {
+        self.snakes.clear();
+        for player in 0..players {
+            let snake = self.start_snake(player);
+            self.snakes.push(snake);
+        }
+    }
+
+    fn start_snake(&self, player: usize) -> Snake {
+        let start = &self.rules.start;
+        // a start from the rules that is off a smaller board is ignored, like on a level without one
+        let mut head = self.level.start
+            .or(start.position.filter(|position| self.level.contains(*position)))
+            .unwrap_or(Position { x: self.level.width / 5, y: self.level.height / 5 });
+        let mut direction = start.direction;
+        // the others start in the remaining corners: mirrored through the center, then across each axis
+        if player % 4 == 1 || player % 4 == 3 {
+            head.x = self.level.width - 1 - head.x;
+            direction = direction.opposite();
+        }
+        if player % 4 == 1 || player % 4 == 2 {
+            head.y = self.level.height - 1 - head.y;
+        }
+        // on a level that isn't symmetric the mirrored start can be a wall or another snake, the closest free cell with
+        // room to move on is taken instead
+        let taken = |position: &Position| self.level.walls.contains(position) || self.is_snake(position);
+        if taken(&head) {
+            let blocked_ahead = |cell: Position| self.next_position(cell, direction).is_none_or(|next| taken(&next));
+            let distance = |cell: Position| cell.x.abs_diff(head.x) as u32 + cell.y.abs_diff(head.y) as u32;
+            head = (0..self.level.width)
+                .cartesian_product(0..self.level.height)
+                .map(|(x, y)| Position { x, y })
+                .filter(|cell| !taken(cell))
+                .min_by_key(|cell| (blocked_ahead(*cell), distance(*cell)))
+                .unwrap_or(head);
+        }
+
+        let mut segments = VecDeque::from([head]);
+        while segments.len() < usize::from(start.length.max(1)) {
+            let tail = *segments.back().unwrap();
+            match self.next_position(tail, direction.opposite()) {
+                Some(next) if !taken(&next) && !segments.contains(&next) => segments.push_back(next),
+                _ => break,
+            }
+        }
+
+        Snake { segments, heading: direction, pending_growth: 0, power_ups: PowerUps::default() }
+    }
+
+    fn eat(&mut self, player: usize, food: PlacedFood, events: &mut Vec<SimEvent>) {
+        let kind = self.rules.food.kind(food.food_type);
+        let snake = &mut self.snakes[player];
+        let mut points = kind.score;
+        for effect in kind.effects {
+            match effect {
+                FoodEffect::SpeedUp => self.speed.speed_up(),
+                FoodEffect::ResetSpeed => self.speed.reset(),
+                FoodEffect::ShrinkTail(count) => {
+                    for _ in 0..count {
+                        if snake.segments.len() <= 1 {
+                            break;
+                        }
+                        if let Some(position) = snake.segments.pop_back() {
+                            events.push(SimEvent::SegmentRemoved { player, position });
+                        }
+                    }
+                }
+                FoodEffect::BonusPoints(bonus) => points += bonus,
+                FoodEffect::Invincibility(ticks) => snake.power_ups.activate(PowerUp::Invincible, ticks),
+                FoodEffect::PowerUp(power_up, ticks) => snake.power_ups.activate(power_up, ticks),
+            }
+        }
+        if snake.power_ups.is_active(PowerUp::ScoreMultiplier) {
+            points *= self.rules.power_ups.score_multiplier;
+        }
+
+        events.push(SimEvent::FoodEaten { player, position: food.position, food_type: food.food_type, points });
+        events.push(SimEvent::SpeedChanged(self.period()));
+    }
+
+    fn next_food_type<R: Rng + ?Sized>(&self, rng: &mut R) -> FoodType {
+        let kinds = &self.rules.food.kinds;
+        (0..kinds.len()).collect::<Vec<_>>()
+            .choose_weighted(rng, |&index| kinds[index].weight)
+            .map_or(FoodType(0), |&index| FoodType(index))
+    }
+
+    fn age_food(&mut self, events: &mut Vec<SimEvent>) {
+        for food in self.food.iter_mut() {
+            if let Some(expires_in) = food.expires_in.as_mut() {
+                *expires_in = expires_in.saturating_sub(1);
+            }
+        }
+        self.food.retain(|food| {
+            let expired = food.expires_in == Some(0);
+            if expired {
+                events.push(SimEvent::FoodExpired { position: food.position });
+            }
+            !expired
+        });
+    }
+
+    fn pull_food(&mut self, events: &mut Vec<SimEvent>) {
+        let radius = i16::from(self.rules.power_ups.magnet_radius);
+        let heads = self.snakes.iter()
+            .filter(|snake| snake.power_ups.is_active(PowerUp::Magnet))
+            .filter_map(|snake| snake.segments.front().copied())
+            .collect::<Vec<_>>();
+        for head in heads {
+            for index in 0..self.food.len() {
+                let from = self.food[index].position;
+                let (dx, dy) = (i16::from(head.x) - i16::from(from.x), i16::from(head.y) - i16::from(from.y));
+                if dx.abs() + dy.abs() > radius {
+                    continue;
+                }
+                let to = if dx.abs() >= dy.abs() {
+                    Position { x: (i16::from(from.x) + dx.signum()) as u8, y: from.y }
+                } else {
+                    Position { x: from.x, y: (i16::from(from.y) + dy.signum()) as u8 }
+                };
+                if self.level.walls.contains(&to) || self.is_snake(&to) || self.food.iter().any(|food| food.position == to) {
+                    continue;
+                }
+                self.food[index].position = to;
+                events.push(SimEvent::FoodMoved { from, to });
+            }
+        }
+    }
+
+    fn refill_food<R: Rng + ?Sized>(&mut self, rng: &mut R, events: &mut Vec<SimEvent>) {
+        while self.food.len() < usize::from(self.rules.food.count) {
+            let food_type = self.next_food_type(rng);
+            if !self.spawn_food(food_type, rng, events) {
+                break;
+            }
+        }
+    }
+
+    fn spawn_food<R: Rng + ?Sized>(&mut self, food_type: FoodType, rng: &mut R, events: &mut Vec<SimEvent>) -> bool {
+        let Some(&position) = self.free_cells().choose(rng) else {
+            return false;
+        };
+        let expires_in = self.rules.food.kind(food_type).lifetime;
+        self.food.push(PlacedFood { position, food_type, expires_in });
+        events.push(SimEvent::FoodSpawned { position, food_type });
+        true
+    }
+
+    fn finish(&mut self, reason: GameOverReason, events: &mut Vec<SimEvent>) {
+        self.game_over = Some(reason);
+        events.push(SimEvent::GameOver(reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use super::*;
+
+    fn rules(x: u8, y: u8, length: u8) -> GameRules {
+        let mut rules = GameRules::default();
+        rules.start.position = Some(Position { x, y });
+        rules.start.length = length;
+        rules.start.direction = Direction::Right;
+        rules.edge_policy = EdgePolicy::Solid;
+        rules.food.count = 1;
+        rules
+    }
+
+    fn place_food(sim: &mut SnakeSim, x: u8, y: u8, food_type: usize) {
+        sim.food = vec![PlacedFood { position: Position { x, y }, food_type: FoodType(food_type), expires_in: None }];
+    }
+
+    fn play(sim: &mut SnakeSim, inputs: &[Direction], rng: &mut StdRng) -> Vec<SimEvent> {
+        inputs.iter().flat_map(|input| sim.step(&[*input], rng)).collect()
+    }
+
+    #[test]
+    fn hitting_a_wall_ends_the_game() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut sim = SnakeSim::new(Level::open(5, 5), rules(3, 2, 2), 1);
+        place_food(&mut sim, 0, 4, 0);
+
+        sim.step(&[Direction::Right], &mut rng);
+        assert_eq!(sim.game_over, None);
+        let events = sim.step(&[Direction::Right], &mut rng);
+        assert_eq!(sim.game_over, Some(GameOverReason::HitWall));
+        assert!(events.contains(&SimEvent::GameOver(GameOverReason::HitWall)));
+    }
+
+    #[test]
+    fn wrapping_edges_never_end_the_game() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut rules = rules(3, 2, 2);
+        rules.edge_policy = EdgePolicy::Wrap;
+        let mut sim = SnakeSim::new(Level::open(5, 5), rules, 1);
+        place_food(&mut sim, 0, 4, 0);
+
+        play(&mut sim, &[Direction::Right; 2], &mut rng);
+        assert_eq!(sim.game_over, None);
+        assert_eq!(sim.snakes[0].segments[0], Position { x: 0, y: 2 });
+    }
+
+    #[test]
+    fn biting_yourself_ends_the_game() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut sim = SnakeSim::new(Level::open(10, 10), rules(5, 5, 5), 1);
+        place_food(&mut sim, 9, 0, 0);
+
+        play(&mut sim, &[Direction::Up, Direction::Left], &mut rng);
+        assert_eq!(sim.game_over, None);
+        sim.step(&[Direction::Down], &mut rng);
+        assert_eq!(sim.game_over, Some(GameOverReason::HitSnake));
+    }
+
+    #[test]
+    fn turning_back_is_ignored() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut sim = SnakeSim::new(Level::open(10, 10), rules(5, 5, 3), 1);
+        place_food(&mut sim, 0, 0, 0);
+
+        sim.step(&[Direction::Left], &mut rng);
+        assert_eq!(sim.game_over, None);
+        assert_eq!(sim.snakes[0].segments[0], Position { x: 6, y: 5 });
+    }
+
+    #[test]
+    fn eating_grows_the_snake() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut sim = SnakeSim::new(Level::open(10, 10), rules(5, 5, 2), 1);
+        place_food(&mut sim, 6, 5, 0);
+
+        let events = sim.step(&[Direction::Right], &mut rng);
+        assert_eq!(sim.snakes[0].segments.len(), 3);
+        assert!(events.iter().any(|event| matches!(event, SimEvent::FoodEaten { player: 0, points: 1, .. })));
+        assert!(events.iter().any(|event| matches!(event, SimEvent::SnakeMoved { tail: None, .. })));
+        // a new apple is placed right away, never on the snake
+        assert_eq!(sim.food.len(), 1);
+        assert!(!sim.is_snake(&sim.food[0].position));
+    }
+
+    #[test]
+    fn apples_speed_up_and_speed_stoppers_reset() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut sim = SnakeSim::new(Level::open(10, 10), rules(2, 5, 2), 1);
+        let start = sim.speed.current;
+
+        place_food(&mut sim, 3, 5, 0);
+        let events = sim.step(&[Direction::Right], &mut rng);
+        let faster = start - Duration::from_millis(sim.rules.speed.step_millis);
+        assert_eq!(sim.speed.current, faster);
+        assert!(events.contains(&SimEvent::SpeedChanged(faster)));
+
+        place_food(&mut sim, 4, 5, 1);
+        let events = sim.step(&[Direction::Right], &mut rng);
+        assert_eq!(sim.speed.current, start);
+        assert!(events.contains(&SimEvent::SpeedChanged(start)));
+    }
+
+    #[test]
+    fn a_start_off_a_smaller_board_falls_back_to_the_corners() {
+        let sim = SnakeSim::new(Level::open(10, 5), rules(12, 2, 2), 2);
+        assert_eq!(sim.snakes[0].segments[0], Position { x: 2, y: 1 });
+        assert_eq!(sim.snakes[1].segments[0], Position { x: 7, y: 3 });
+    }
+
+    #[test]
+    fn mirrored_starts_avoid_walls_and_other_snakes() {
+        let level = Level::parse("
+            .....
+            .S.#.
+            .....
+        ").unwrap();
+        let sim = SnakeSim::new(level, rules(0, 0, 2), 4);
+        for (player, snake) in sim.snakes.iter().enumerate() {
+            let head = snake.segments[0];
+            assert!(!sim.level.walls.contains(&head), "player {} starts in a wall", player);
+            let others = sim.snakes.iter().enumerate().filter(|(other, _)| *other != player);
+            assert!(!others.flat_map(|(_, other)| other.segments.iter()).any(|segment| snake.segments.contains(segment)));
+        }
+        assert_eq!(sim.snakes[1].segments[0], Position { x: 3, y: 0 });
+    }
+
+    #[test]
+    fn speed_never_drops_below_the_minimum() {
+        let mut speed = Speed::new(&SpeedRules { start_millis: 200, min_millis: 120, step_millis: 50 });
+        speed.speed_up();
+        speed.speed_up();
+        speed.speed_up();
+        assert_eq!(speed.current, Duration::from_millis(120));
+        speed.reset();
+        assert_eq!(speed.current, Duration::from_millis(200));
+    }
+
+    #[test]
+    fn filling_the_board_wins() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let mut sim = SnakeSim::new(Level::open(3, 1), rules(1, 0, 2), 1);
+        assert_eq!(sim.level.cell_count(), 3);
+        place_food(&mut sim, 2, 0, 0);
+
+        let events = sim.step(&[Direction::Right], &mut rng);
+        assert_eq!(sim.game_over, Some(GameOverReason::Win));
+        assert!(events.contains(&SimEvent::GameOver(GameOverReason::Win)));
+    }
+
+    #[test]
+    fn walls_are_not_counted_as_cells_to_fill() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut level = Level::open(4, 1);
+        level.walls.insert(Position { x: 3, y: 0 });
+        let mut sim = SnakeSim::new(level, rules(1, 0, 2), 1);
+        place_food(&mut sim, 2, 0, 0);
+
+        sim.step(&[Direction::Right], &mut rng);
+        assert_eq!(sim.game_over, Some(GameOverReason::Win));
+    }
+
+    #[test]
+    fn a_crashed_opponent_leaves_the_board() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut sim = SnakeSim::new(Level::open(10, 10), rules(2, 2, 2), 2).with_opponents(1);
+        place_food(&mut sim, 0, 9, 0);
+        assert_eq!(sim.snakes[1].segments[0], Position { x: 7, y: 7 });
+
+        sim.step(&[Direction::Right, Direction::Up], &mut rng);
+        sim.step(&[Direction::Right, Direction::Up], &mut rng);
+        let events = sim.step(&[Direction::Right, Direction::Up], &mut rng);
+        assert_eq!(sim.game_over, None);
+        assert!(sim.snakes[1].is_out());
+        assert_eq!(events.iter().filter(|event| matches!(event, SimEvent::SegmentRemoved { player: 1, .. })).count(), 2);
+
+        sim.step(&[Direction::Right, Direction::Up], &mut rng);
+        assert_eq!(sim.snakes[0].segments[0], Position { x: 6, y: 2 });
+        assert!(sim.snakes[1].is_out());
+    }
+
+    #[test]
+    fn crashing_with_opponents_ends_a_solo_game() {
+        let mut rng = StdRng::seed_from_u64(12);
+        let mut sim = SnakeSim::new(Level::open(10, 10), rules(2, 2, 2), 2).with_opponents(1);
+        place_food(&mut sim, 0, 9, 0);
+
+        for _ in 0..3 {
+            sim.step(&[Direction::Down, Direction::Left], &mut rng);
+        }
+        assert_eq!(sim.game_over, Some(GameOverReason::HitWall));
+    }
+
+    #[test]
+    fn meeting_head_on_is_a_draw() {
+        let mut rng = StdRng::seed_from_u64(8);
+        let mut sim = SnakeSim::new(Level::open(7, 3), rules(1, 1, 1), 2);
+        assert_eq!(sim.snakes[1].segments[0], Position { x: 5, y: 1 });
+        place_food(&mut sim, 0, 0, 0);
+
+        sim.step(&[Direction::Right, Direction::Left], &mut rng);
+        assert_eq!(sim.game_over, None);
+        sim.step(&[Direction::Right, Direction::Left], &mut rng);
+        assert_eq!(sim.game_over, Some(GameOverReason::Draw));
+    }
+
+    #[test]
+    fn the_snake_left_in_the_game_wins() {
+        let mut rng = StdRng::seed_from_u64(9);
+        let mut sim = SnakeSim::new(Level::open(10, 10), rules(2, 2, 2), 2);
+        place_food(&mut sim, 0, 9, 0);
+
+        // the second snake starts at the top right and runs up into the edge
+        sim.step(&[Direction::Right, Direction::Up], &mut rng);
+        sim.step(&[Direction::Right, Direction::Up], &mut rng);
+        assert_eq!(sim.game_over, None);
+        sim.step(&[Direction::Right, Direction::Up], &mut rng);
+        assert_eq!(sim.game_over, Some(GameOverReason::Winner(0)));
+    }
+
+    #[test]
+    fn running_into_the_other_snake_loses() {
+        let mut rng = StdRng::seed_from_u64(10);
+        let mut sim = SnakeSim::new(Level::open(7, 5), rules(2, 1, 1), 2);
+        place_food(&mut sim, 0, 0, 0);
+        sim.snakes[1].segments = (0..4).rev().map(|y| Position { x: 3, y }).collect();
+        sim.snakes[1].heading = Direction::Up;
+
+        sim.step(&[Direction::Right, Direction::Up], &mut rng);
+        assert_eq!(sim.game_over, Some(GameOverReason::Winner(1)));
+    }
+
+    #[test]
+    fn the_same_seed_plays_the_same_game() {
+        let play_seeded = || {
+            let mut rng = StdRng::seed_from_u64(11);
+            let mut sim = SnakeSim::new(Level::open(12, 12), rules(2, 2, 3), 1);
+            sim.reset(&mut rng);
+            for tick in 0..40 {
+                let input = [Direction::Up, Direction::Right, Direction::Down, Direction::Right][tick / 3 % 4];
+                sim.step(&[input], &mut rng);
+            }
+            sim.state_hash()
+        };
+        assert_eq!(play_seeded(), play_seeded());
+    }
+}
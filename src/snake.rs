@@ -1,20 +1,42 @@
 use std::collections::VecDeque;
-use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
 
 use crate::board::Position;
+use crate::powerup::PowerUps;
 
-#[derive(Resource, Debug)]
+#[derive(Debug, Clone)]
 pub struct Snake {
     pub segments: VecDeque<Position>,
+    pub heading: Direction,
+    pub pending_growth: u32,
+    pub power_ups: PowerUps,
 }
 
-impl Default for Snake {
-    fn default() -> Self {
-        Self {
-            segments: VecDeque::from([
-                Position { x: 4, y: 4 },
-                Position { x: 3, y: 4 },
-            ]),
+impl Snake {
+    // an opponent that crashed is taken off the board, its score stays
+    pub fn is_out(&self) -> bool {
+        self.segments.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    #[default]
+    Right
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
         }
     }
 }
@@ -0,0 +1,293 @@
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use tungstenite::client::IntoClientRequest;
+use tungstenite::handshake::server::{NoCallback, ServerHandshake};
+use tungstenite::handshake::{HandshakeError, MidHandshake};
+use tungstenite::{Message, WebSocket};
+
+use crate::board::{BoardView, Position};
+use crate::controls::Player;
+use crate::food::FoodType;
+use crate::level::{Level, LevelError};
+use crate::rules::GameRules;
+use crate::scoring::Score;
+use crate::sim::{PlacedFood, SnakeSim};
+use crate::{GameState, Notice};
+
+pub const DEFAULT_PORT: u16 = 7780;
+
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpectatorConfig>()
+            .add_systems(Startup, open_stream)
+            .add_systems(Update, watch_from_config.run_if(run_once()))
+            .add_systems(Update, accept_spectators.run_if(resource_exists::<Stream>()))
+            .add_systems(FixedUpdate, send_frame
+                .after(crate::tick)
+                .run_if(resource_exists::<Stream>().and_then(resource_changed::<SnakeSim>())))
+            .add_systems(OnEnter(GameState::Spectating), start_watching)
+            .add_systems(Update, watch_frames.run_if(in_state(GameState::Spectating)))
+            .add_systems(OnExit(GameState::Spectating), stop_watching);
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct SpectatorConfig {
+    // the port this game is streamed on, and the stream to watch instead of playing
+    pub port: u16,
+    pub watch: Option<String>,
+}
+
+impl Default for SpectatorConfig {
+    fn default() -> Self {
+        Self { port: DEFAULT_PORT, watch: None }
+    }
+}
+
+// the board as it is after a tick, sent to every spectator as one JSON text message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    pub width: u8,
+    pub height: u8,
+    pub walls: Vec<Position>,
+    pub snakes: Vec<SnakeFrame>,
+    pub food: Vec<FoodFrame>,
+    pub speed_millis: u64,
+    pub game_over: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnakeFrame {
+    // head first
+    pub segments: Vec<Position>,
+    pub score: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FoodFrame {
+    pub position: Position,
+    // index into the food kinds of `rules.ron`
+    pub food_type: usize,
+}
+
+impl Frame {
+    pub fn new(sim: &SnakeSim, score: &Score) -> Self {
+        let mut walls = sim.level.walls.iter().copied().collect::<Vec<_>>();
+        walls.sort_by_key(|wall| (wall.y, wall.x));
+        Self {
+            width: sim.level.width,
+            height: sim.level.height,
+            walls,
+            snakes: sim.snakes.iter().enumerate()
+                .map(|(player, snake)| SnakeFrame { segments: snake.segments.iter().copied().collect(), score: score.of(player) })
+                .collect(),
+            food: sim.food.iter()
+                .map(|food| FoodFrame { position: food.position, food_type: food.food_type.0 })
+                .collect(),
+            speed_millis: sim.speed.current.as_millis() as u64,
+            game_over: sim.game_over.map(|reason| format!("{:?}", reason)),
+        }
+    }
+
+    // a sim that draws like the streamed board, it is never stepped
+    pub fn to_sim(&self, rules: &GameRules) -> Result<SnakeSim, LevelError> {
+        let level = Level { width: self.width, height: self.height, walls: self.walls.iter().copied().collect(), start: None };
+        level.check()?;
+        let mut sim = SnakeSim::new(level, rules.clone(), self.snakes.len());
+        sim.snakes.truncate(self.snakes.len());
+        for (snake, frame) in sim.snakes.iter_mut().zip(self.snakes.iter()) {
+            snake.segments = frame.segments.iter().copied().collect();
+        }
+        sim.food = self.food.iter()
+            .map(|food| PlacedFood { position: food.position, food_type: FoodType(food.food_type), expires_in: None })
+            .collect();
+        Ok(sim)
+    }
+}
+
+// spectators still shaking hands and the ones frames are sent to
+#[derive(Resource)]
+struct Stream {
+    listener: TcpListener,
+    handshakes: Vec<MidHandshake<ServerHandshake<TcpStream, NoCallback>>>,
+    spectators: Vec<WebSocket<TcpStream>>,
+}
+
+// a game that watches another one doesn't stream itself, so both can run on one machine
+fn open_stream(mut commands: Commands, config: Res<SpectatorConfig>) {
+    if config.watch.is_some() {
+        return;
+    }
+    let listener = TcpListener::bind(("0.0.0.0", config.port)).and_then(|listener| {
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    });
+    match listener {
+        Ok(listener) => {
+            info!("streaming to spectators on port {}", config.port);
+            commands.insert_resource(Stream { listener, handshakes: vec![], spectators: vec![] });
+        }
+        Err(err) => warn!("can't stream to spectators on port {}: {}", config.port, err),
+    }
+}
+
+fn accept_spectators(mut stream: ResMut<Stream>) {
+    let stream = &mut *stream;
+    let mut handshakes = std::mem::take(&mut stream.handshakes)
+        .into_iter()
+        .map(MidHandshake::handshake)
+        .collect::<Vec<_>>();
+    loop {
+        match stream.listener.accept() {
+            Ok((socket, address)) => {
+                info!("spectator {} connected", address);
+                match socket.set_nonblocking(true) {
+                    Ok(()) => handshakes.push(tungstenite::accept(socket)),
+                    Err(err) => warn!("{}", err),
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+            Err(err) => {
+                warn!("{}", err);
+                break;
+            }
+        }
+    }
+
+    // a handshake that can't finish without waiting for the spectator is picked up again next frame
+    for handshake in handshakes {
+        match handshake {
+            Ok(spectator) => stream.spectators.push(spectator),
+            Err(HandshakeError::Interrupted(handshake)) => stream.handshakes.push(handshake),
+            Err(HandshakeError::Failure(err)) => warn!("spectator handshake failed: {}", err),
+        }
+    }
+
+    // reading answers pings and notices spectators that left
+    stream.spectators.retain_mut(|spectator| loop {
+        match spectator.read() {
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => break true,
+            Err(_) => break false,
+        }
+    });
+}
+
+fn send_frame(mut stream: ResMut<Stream>, sim: Res<SnakeSim>, score: Res<Score>) {
+    if stream.spectators.is_empty() {
+        return;
+    }
+    let frame = serde_json::to_string(&Frame::new(&sim, &score)).expect("frames serialize to json");
+    // a frame the socket can't take right away is queued and goes out with the next one
+    stream.spectators.retain_mut(|spectator| match spectator.send(Message::Text(frame.clone())) {
+        Ok(()) => true,
+        Err(tungstenite::Error::Io(err)) => err.kind() == ErrorKind::WouldBlock,
+        Err(_) => false,
+    });
+}
+
+#[derive(Resource)]
+struct Watching(WebSocket<TcpStream>);
+
+// the handshake is done before the socket stops blocking, watching starts with a connected stream
+fn connect(url: &str) -> Result<WebSocket<TcpStream>, String> {
+    let request = url.into_client_request().map_err(|err| err.to_string())?;
+    let host = request.uri().host().unwrap_or("127.0.0.1").to_string();
+    let port = request.uri().port_u16().unwrap_or(DEFAULT_PORT);
+    let stream = TcpStream::connect((host, port)).map_err(|err| err.to_string())?;
+    let (socket, _) = tungstenite::client(request, stream).map_err(|err| err.to_string())?;
+    socket.get_ref().set_nonblocking(true).map_err(|err| err.to_string())?;
+    Ok(socket)
+}
+
+// switched to only after the first frame, the game started on launch would take over otherwise
+fn watch_from_config(config: Res<SpectatorConfig>, mut next_state: ResMut<NextState<GameState>>) {
+    if config.watch.is_some() {
+        next_state.set(GameState::Spectating);
+    }
+}
+
+fn start_watching(
+    mut commands: Commands,
+    config: Res<SpectatorConfig>,
+    players: Query<Entity, With<Player>>,
+    mut notices: EventWriter<Notice>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(url) = config.watch.as_deref() else {
+        next_state.set(GameState::Menu);
+        return;
+    };
+    match connect(url) {
+        Ok(socket) => {
+            for entity in players.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            commands.insert_resource(Watching(socket));
+            notices.send(Notice(format!("Watching {}, Esc stops", url)));
+        }
+        Err(err) => {
+            warn!("{}", err);
+            notices.send(Notice(format!("Can't watch {}: {}", url, err)));
+            next_state.set(GameState::Menu);
+        }
+    }
+}
+
+// only the newest frame is drawn, the board is simply redrawn from it
+fn watch_frames(
+    watching: Option<ResMut<Watching>>,
+    keys: Res<Input<KeyCode>>,
+    mut board: BoardView,
+    mut sim: ResMut<SnakeSim>,
+    mut score: ResMut<Score>,
+    rules: Res<GameRules>,
+    mut notices: EventWriter<Notice>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(mut watching) = watching else {
+        return;
+    };
+    if keys.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::Menu);
+        return;
+    }
+
+    let mut latest = None;
+    loop {
+        match watching.0.read() {
+            Ok(Message::Text(text)) => match serde_json::from_str::<Frame>(&text) {
+                Ok(frame) => latest = Some(frame),
+                Err(err) => warn!("not a frame: {}", err),
+            },
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => break,
+            Err(err) => {
+                notices.send(Notice(format!("The stream ended: {}", err)));
+                next_state.set(GameState::Menu);
+                break;
+            }
+        }
+    }
+
+    match latest.map(|frame| (frame.to_sim(&rules), frame)) {
+        Some((Ok(streamed), frame)) => {
+            *sim = streamed;
+            board.redraw(&sim);
+            *score = Score::new(frame.snakes.len());
+            for (player, snake) in frame.snakes.iter().enumerate() {
+                score.add(player, snake.score);
+            }
+        }
+        Some((Err(err), _)) => warn!("not a board: {}", err),
+        None => {}
+    }
+}
+
+fn stop_watching(mut commands: Commands) {
+    commands.remove_resource::<Watching>();
+}
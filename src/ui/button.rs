@@ -1,6 +1,11 @@
 use bevy::app::AppExit;
 use bevy::prelude::*;
 use crate::GameState;
+use crate::level::Levels;
+use crate::net::NetRequest;
+use crate::replay::ReplayRequest;
+use crate::rules::GameRules;
+use crate::settings::{Settings, MAX_PLAYERS};
 
 
 const NORMAL_BUTTON: Color = Color::Hsla {
@@ -33,7 +38,12 @@ pub fn text_button_system(
     >,
     text_query: Query<&Text>,
     mut exit: EventWriter<AppExit>,
-    mut next_state: ResMut<NextState<GameState>>
+    mut next_state: ResMut<NextState<GameState>>,
+    mut settings: ResMut<Settings>,
+    mut replay_requests: EventWriter<ReplayRequest>,
+    mut net_requests: EventWriter<NetRequest>,
+    levels: Res<Levels>,
+    rules: Res<GameRules>,
 ) {
     for (interaction, mut color, children) in &mut interaction_query {
         let text = text_query.get(children[0]).unwrap();
@@ -41,8 +51,32 @@ pub fn text_button_system(
             Interaction::Pressed => {
                 *color = PRESSED_BUTTON.into();
                 match text.sections[0].value.as_str() {
-                    "New Game" => { next_state.set(GameState::Playing) }
+                    "New Game" => {
+                        settings.players = 1;
+                        next_state.set(GameState::Starting)
+                    }
+                    "Versus" => {
+                        settings.players = MAX_PLAYERS;
+                        next_state.set(GameState::Starting)
+                    }
+                    "Retry" | "Restart" => { next_state.set(GameState::Starting) }
+                    "Resume" => { next_state.set(GameState::Playing) }
+                    "Watch Replay" => { next_state.set(GameState::Replay) }
+                    "Save Replay" => { replay_requests.send(ReplayRequest::Save) }
+                    "Load Replay" => { replay_requests.send(ReplayRequest::Load(None)) }
+                    "Host Game" => { net_requests.send(NetRequest::Host) }
+                    "Join Game" => { net_requests.send(NetRequest::Join) }
+                    "Main Menu" | "Quit" => { next_state.set(GameState::Menu) }
                     "Exit" => { exit.send(AppExit) }
+                    label if label.starts_with("Walls: ") => { settings.edge_policy = Some(rules.with_settings(&settings).edge_policy.toggled()) }
+                    label if label.starts_with("Board: ") => { settings.next_board_size() }
+                    label if label.starts_with("Level: ") => { settings.next_level(levels.paths()) }
+                    label if label.starts_with("Opponents: ") => { settings.next_opponents() }
+                    label if label.starts_with("AI: ") => { settings.difficulty = settings.difficulty.next() }
+                    label if label.starts_with("Food: ") => {
+                        let current = rules.with_settings(&settings).food.count;
+                        settings.next_food_count(current)
+                    }
                     _ => { unimplemented!("Button goes nowhere") }
                 }
             }
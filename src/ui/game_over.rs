@@ -0,0 +1,133 @@
+use bevy::prelude::*;
+use crate::GameState;
+use crate::rng::GameRng;
+use crate::scoring::{Score, Timer};
+use crate::sim::{GameOverReason, SnakeSim};
+use crate::ui::button;
+
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, game_over_ui)
+            .add_systems(OnEnter(GameState::GameOver), show_game_over)
+            .add_systems(OnExit(GameState::GameOver), hide_game_over);
+    }
+}
+
+#[derive(Component)]
+struct GameOverScreen;
+
+#[derive(Component)]
+struct ReasonText;
+
+#[derive(Component)]
+struct SummaryText;
+
+#[derive(Component)]
+struct RecordText;
+
+fn show_game_over(
+    mut screen: Query<&mut Visibility, With<GameOverScreen>>,
+    mut reason_text: Query<&mut Text, (With<ReasonText>, Without<SummaryText>, Without<RecordText>)>,
+    mut summary_text: Query<&mut Text, (With<SummaryText>, Without<ReasonText>, Without<RecordText>)>,
+    mut record_text: Query<&mut Text, (With<RecordText>, Without<ReasonText>, Without<SummaryText>)>,
+    sim: Res<SnakeSim>,
+    score: Res<Score>,
+    timer: Res<Timer>,
+    rng: Res<GameRng>,
+) {
+    *screen.single_mut() = Visibility::Visible;
+
+    reason_text.single_mut().sections[0].value = match sim.game_over {
+        Some(GameOverReason::HitWall) => "You hit the wall".to_string(),
+        Some(GameOverReason::HitSnake) => "You ran into a snake".to_string(),
+        Some(GameOverReason::Win) => "You won!".to_string(),
+        Some(GameOverReason::Winner(player)) => format!("Player {} wins!", player + 1),
+        Some(GameOverReason::Draw) => "Draw!".to_string(),
+        None => "Game over".to_string(),
+    };
+
+    let elapsed = timer.runtime.unwrap_or_default().as_secs();
+    let scores = if score.rivals.is_empty() {
+        format!("Score: {}   Length: {}", score.score, sim.snakes[0].segments.len())
+    } else {
+        (0..sim.snakes.len())
+            .map(|player| format!("Player {}: {}", player + 1, score.of(player)))
+            .collect::<Vec<_>>()
+            .join("   ")
+    };
+    summary_text.single_mut().sections[0].value = format!(
+        "{}   Time: {}s\nSeed: {}",
+        scores, elapsed, rng.seed()
+    );
+
+    record_text.single_mut().sections[0].value = if score.new_record {
+        "New high score!".to_string()
+    } else {
+        String::new()
+    };
+}
+
+fn hide_game_over(mut screen: Query<&mut Visibility, With<GameOverScreen>>) {
+    *screen.single_mut() = Visibility::Hidden;
+}
+
+fn game_over_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    let text_style = |font_size: f32| TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size,
+        color: Color::rgb(0.1, 0.1, 0.1),
+    };
+
+    commands.spawn((NodeBundle {
+        background_color: BackgroundColor(Color::Hsla {
+            hue: 0.0,
+            saturation: 0.0,
+            lightness: 100.0,
+            alpha: 0.2,
+        }),
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            position_type: PositionType::Absolute,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        visibility: Visibility::Hidden,
+        ..default()
+    }, GameOverScreen))
+        .with_children(|parent| {
+            parent.spawn(NodeBundle {
+                background_color: BackgroundColor( Color::Hsla { hue: 0.0, saturation: 0.0, lightness: 100.0, alpha: 0.4 }),
+                style: Style {
+                    width: Val::Px(500.0),
+                    height: Val::Px(580.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::SpaceEvenly,
+                    align_items: AlignItems::Center,
+                    position_type: PositionType::Absolute,
+                    align_self: AlignSelf::Center,
+                    border: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                ..default()
+            }).with_children(|parent| {
+                parent.spawn((TextBundle::from_section("", text_style(40.0)), ReasonText));
+                parent.spawn((
+                    TextBundle::from_section("", text_style(22.0)).with_text_alignment(TextAlignment::Center),
+                    SummaryText
+                ));
+                parent.spawn((TextBundle::from_section("", text_style(26.0)), RecordText));
+                button::spawn_button(parent, &asset_server, "Retry");
+                button::spawn_button(parent, &asset_server, "Watch Replay");
+                button::spawn_button(parent, &asset_server, "Save Replay");
+                button::spawn_button(parent, &asset_server, "Main Menu");
+            });
+        });
+}
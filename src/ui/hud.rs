@@ -2,13 +2,16 @@ use bevy::prelude::*;
 use crate::scoring::Score as ScoringScore;
 use crate::scoring::Timer as ScoringTimer;
 use crate::scoring::HighScore as ScoringHighScore;
+use crate::ghost::Ghost;
+use crate::powerup::PowerUp;
+use crate::sim::SnakeSim;
 
 pub struct HudPlugin;
 
 impl Plugin for HudPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, build_hud)
-            .add_systems(Update, (update_score, update_high_score));
+            .add_systems(Update, (update_score, update_high_score, update_ghost_delta, update_food_count, update_power_ups));
     }
 }
 
@@ -24,6 +27,15 @@ pub struct Timer;
 #[derive(Component)]
 pub struct HighTimer;
 
+#[derive(Component)]
+pub struct GhostDelta;
+
+#[derive(Component)]
+pub struct FoodCount;
+
+#[derive(Component)]
+pub struct PowerUpIndicator(PowerUp);
+
 fn build_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.spawn(NodeBundle {
         background_color: HUD_COLOR.into(),
@@ -47,6 +59,12 @@ fn build_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
                         text: text_section("0000", &asset_server, 18.0, Color::WHITE).with_alignment(TextAlignment::Center),
                         ..default()
                     }).insert(Timer);
+
+                    build_label(parent, &asset_server, "Food");
+                    parent.spawn(TextBundle {
+                        text: text_section("0", &asset_server, 18.0, Color::WHITE).with_alignment(TextAlignment::Center),
+                        ..default()
+                    }).insert(FoodCount);
                 });
         // build highscore plate
         parent.spawn(NodeBundle{
@@ -65,6 +83,34 @@ fn build_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
                     text: text_section("0000", &asset_server, 18.0, Color::WHITE).with_alignment(TextAlignment::Center),
                     ..default()
                 }).insert(HighTimer);
+
+                build_label(parent, &asset_server, "Ghost");
+                parent.spawn(TextBundle {
+                    text: text_section("-", &asset_server, 18.0, Color::WHITE).with_alignment(TextAlignment::Center),
+                    ..default()
+                }).insert(GhostDelta);
+            });
+        // build active power-ups plate, rows stay hidden until their power-up is active
+        parent.spawn(NodeBundle{
+            style: HUD_INNER_STYLE,
+            ..default() })
+            .with_children(|parent| {
+                for power_up in PowerUp::ALL {
+                    parent.spawn(NodeBundle {
+                        style: POWER_UP_ROW_STYLE,
+                        ..default()
+                    }).with_children(|parent| {
+                        parent.spawn(NodeBundle {
+                            background_color: power_up_color(power_up).into(),
+                            style: POWER_UP_ICON_STYLE,
+                            ..default()
+                        });
+                        parent.spawn(TextBundle {
+                            text: text_section(power_up.label(), &asset_server, 16.0, Color::WHITE),
+                            ..default()
+                        });
+                    }).insert(PowerUpIndicator(power_up));
+                }
             });
     });
 }
@@ -75,12 +121,51 @@ fn update_score(
     mut query_score_hud: Query<(&Score, &mut Text), Without<Timer>>,
     mut query_timer_hud: Query<(&Timer, &mut Text), Without<Score>>
 ) {
-    query_score_hud.single_mut().1.sections[0].value = format!("{:04}", score.score);
+    // in versus every player's score is listed, separated by a bar
+    query_score_hud.single_mut().1.sections[0].value = (0..=score.rivals.len())
+        .map(|player| format!("{:04}", score.of(player)))
+        .collect::<Vec<_>>()
+        .join(" | ");
 
-    match *timer {
-        ScoringTimer { start: _, runtime: Some(val) } => { query_timer_hud.single_mut().1.sections[0].value = format!("{}", val.as_secs())}
-        ScoringTimer { start: Some(val), runtime: None } => { query_timer_hud.single_mut().1.sections[0].value = format!("{}", val.elapsed().as_secs())}
-        _ => {}
+    if let Some(elapsed) = timer.elapsed() {
+        query_timer_hud.single_mut().1.sections[0].value = format!("{}", elapsed.as_secs());
+    }
+}
+
+fn update_food_count(
+    sim: Res<SnakeSim>,
+    mut query_food_hud: Query<&mut Text, With<FoodCount>>,
+) {
+    query_food_hud.single_mut().sections[0].value = format!("{}", sim.food.len());
+}
+
+fn update_power_ups(
+    sim: Res<SnakeSim>,
+    mut query_indicators: Query<(&PowerUpIndicator, &mut Style, &Children)>,
+    mut query_text: Query<&mut Text>,
+) {
+    for (indicator, mut style, children) in query_indicators.iter_mut() {
+        let remaining = sim.snakes.iter()
+            .filter_map(|snake| snake.power_ups.remaining(indicator.0))
+            .max();
+        style.display = if remaining.is_some() { Display::Flex } else { Display::None };
+        if let Some(remaining) = remaining {
+            for child in children.iter() {
+                if let Ok(mut text) = query_text.get_mut(*child) {
+                    text.sections[0].value = format!("{} {}", indicator.0.label(), remaining);
+                }
+            }
+        }
+    }
+}
+
+fn power_up_color(power_up: PowerUp) -> Color {
+    match power_up {
+        PowerUp::Invincible => Color::rgb(1.0, 0.85, 0.0),
+        PowerUp::SlowMotion => Color::rgb(0.4, 0.7, 1.0),
+        PowerUp::Ghost => Color::rgba(0.9, 0.9, 1.0, 0.6),
+        PowerUp::ScoreMultiplier => Color::rgb(0.2, 0.8, 0.3),
+        PowerUp::Magnet => Color::rgb(0.8, 0.1, 0.2),
     }
 }
 
@@ -93,6 +178,27 @@ fn update_high_score(
     query_hightimer_hud.single_mut().1.sections[0].value = format!("{:04}", highsocre.time.as_secs());
 }
 
+// points ahead of or behind the record run at the same tick
+fn update_ghost_delta(
+    score: Res<ScoringScore>,
+    ghost: Option<Res<Ghost>>,
+    mut query_ghost_hud: Query<&mut Text, With<GhostDelta>>,
+) {
+    let section = &mut query_ghost_hud.single_mut().sections[0];
+    let Some(ghost) = ghost else {
+        section.value = "-".to_string();
+        section.style.color = Color::WHITE;
+        return;
+    };
+    let delta = i64::from(score.score) - i64::from(ghost.score);
+    section.value = format!("{:+}", delta);
+    section.style.color = match delta {
+        0 => Color::WHITE,
+        delta if delta > 0 => GHOST_AHEAD_COLOR,
+        _ => GHOST_BEHIND_COLOR,
+    };
+}
+
 
 
 fn build_label(parent: &mut ChildBuilder, asset_server: &Res<AssetServer>, label: &str) {
@@ -116,6 +222,8 @@ fn text_section(value: &str, asset_server: &Res<AssetServer>, font_size: f32, co
 }
 
 const HUD_COLOR: Color = Color::hsla(0.0, 0.0, 0.8, 1.0);
+const GHOST_AHEAD_COLOR: Color = Color::rgb(0.1, 0.6, 0.2);
+const GHOST_BEHIND_COLOR: Color = Color::rgb(0.8, 0.1, 0.1);
 // const HUD_INNER_COLOR: Color = Color::hsla(0.0, 0.0, 0.9, 1.0);
 const HUD_INNER_STYLE: Style = {
     let mut style = Style::DEFAULT;
@@ -125,10 +233,23 @@ const HUD_INNER_STYLE: Style = {
     style.row_gap =  Val::Px(10.0);
     style
 };
+const POWER_UP_ROW_STYLE: Style = {
+    let mut style = Style::DEFAULT;
+    style.display = Display::None;
+    style.align_items = AlignItems::Center;
+    style.column_gap = Val::Px(6.0);
+    style
+};
+const POWER_UP_ICON_STYLE: Style = {
+    let mut style = Style::DEFAULT;
+    style.width = Val::Px(14.0);
+    style.height = Val::Px(14.0);
+    style
+};
 const HUD_STYLE: Style = {
     let mut style = Style::DEFAULT;
     style.width = Val::Px(200.0);
-    style.height = Val::Px(250.0);
+    style.height = Val::Px(500.0);
     style.position_type = PositionType::Absolute;
     style.right = Val::Percent(2.0);
     style.top = Val::Percent(2.0);
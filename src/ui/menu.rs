@@ -1,5 +1,8 @@
 use bevy::prelude::*;
 use crate::GameState;
+use crate::level::level_name;
+use crate::rules::GameRules;
+use crate::settings::Settings;
 use crate::ui::button;
 
 
@@ -9,8 +12,11 @@ impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, game_ui)
             .add_systems(Update, button::text_button_system)
+            .add_systems(Update, update_settings_labels.run_if(resource_changed::<Settings>().or_else(resource_changed::<GameRules>())))
             .add_systems(OnEnter(GameState::Menu), show_menu)
-            .add_systems(OnEnter(GameState::Playing), hide_menu);
+            .add_systems(OnEnter(GameState::Playing), hide_menu)
+            .add_systems(OnEnter(GameState::Replay), hide_menu)
+            .add_systems(OnEnter(GameState::Spectating), hide_menu);
     }
 }
 
@@ -27,9 +33,53 @@ fn hide_menu(mut menu: Query<&mut Visibility, With<MainMenu>>) {
     *menu = Visibility::Hidden;
 }
 
+fn edge_policy_label(settings: &Settings, rules: &GameRules) -> String {
+    format!("Walls: {}", rules.with_settings(settings).edge_policy)
+}
+
+fn board_size_label(settings: &Settings) -> String {
+    format!("Board: {}x{}", settings.board_width, settings.board_height)
+}
+
+fn food_count_label(settings: &Settings, rules: &GameRules) -> String {
+    format!("Food: {}", rules.with_settings(settings).food.count)
+}
+
+fn opponents_label(settings: &Settings) -> String {
+    format!("Opponents: {}", settings.opponents)
+}
+
+fn difficulty_label(settings: &Settings) -> String {
+    format!("AI: {}", settings.difficulty)
+}
+
+fn level_label(settings: &Settings) -> String {
+    format!("Level: {}", settings.level.as_deref().map_or("Open", level_name))
+}
+
+fn update_settings_labels(settings: Res<Settings>, rules: Res<GameRules>, mut texts: Query<&mut Text>) {
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value.starts_with("Walls: ") {
+            text.sections[0].value = edge_policy_label(&settings, &rules);
+        } else if text.sections[0].value.starts_with("Board: ") {
+            text.sections[0].value = board_size_label(&settings);
+        } else if text.sections[0].value.starts_with("Food: ") {
+            text.sections[0].value = food_count_label(&settings, &rules);
+        } else if text.sections[0].value.starts_with("Level: ") {
+            text.sections[0].value = level_label(&settings);
+        } else if text.sections[0].value.starts_with("Opponents: ") {
+            text.sections[0].value = opponents_label(&settings);
+        } else if text.sections[0].value.starts_with("AI: ") {
+            text.sections[0].value = difficulty_label(&settings);
+        }
+    }
+}
+
 pub fn game_ui(
     mut commands: Commands,
-    asset_server: Res<AssetServer>
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    rules: Res<GameRules>,
 ) {
     commands.spawn((NodeBundle {
         background_color: BackgroundColor(Color::Hsla {
@@ -52,9 +102,9 @@ pub fn game_ui(
             parent.spawn(NodeBundle {
                 background_color: BackgroundColor( Color::Hsla { hue: 0.0, saturation: 0.0, lightness: 100.0, alpha: 0.4 }),
                 style: Style {
-                    width: Val::Px(500.0),
-                    height: Val::Px(350.0),
-                    flex_direction: FlexDirection::Column,
+                    width: Val::Px(900.0),
+                    height: Val::Px(520.0),
+                    flex_direction: FlexDirection::Row,
                     justify_content: JustifyContent::SpaceEvenly,
                     position_type: PositionType::Absolute,
                     align_self: AlignSelf::Center,
@@ -63,8 +113,33 @@ pub fn game_ui(
                 },
                 ..default()
             }).with_children(|parent| {
-                button::spawn_button(parent, &asset_server, "New Game");
-                button::spawn_button(parent, &asset_server, "Exit");
+                // games on the left, settings on the right
+                parent.spawn(NodeBundle { style: MENU_COLUMN_STYLE, ..default() })
+                    .with_children(|parent| {
+                        button::spawn_button(parent, &asset_server, "New Game");
+                        button::spawn_button(parent, &asset_server, "Versus");
+                        button::spawn_button(parent, &asset_server, "Host Game");
+                        button::spawn_button(parent, &asset_server, "Join Game");
+                        button::spawn_button(parent, &asset_server, "Load Replay");
+                        button::spawn_button(parent, &asset_server, "Exit");
+                    });
+                parent.spawn(NodeBundle { style: MENU_COLUMN_STYLE, ..default() })
+                    .with_children(|parent| {
+                        button::spawn_button(parent, &asset_server, &edge_policy_label(&settings, &rules));
+                        button::spawn_button(parent, &asset_server, &board_size_label(&settings));
+                        button::spawn_button(parent, &asset_server, &level_label(&settings));
+                        button::spawn_button(parent, &asset_server, &food_count_label(&settings, &rules));
+                        button::spawn_button(parent, &asset_server, &opponents_label(&settings));
+                        button::spawn_button(parent, &asset_server, &difficulty_label(&settings));
+                    });
             });
         });
-}
\ No newline at end of file
+}
+
+const MENU_COLUMN_STYLE: Style = {
+    let mut style = Style::DEFAULT;
+    style.width = Val::Percent(48.0);
+    style.flex_direction = FlexDirection::Column;
+    style.justify_content = JustifyContent::SpaceEvenly;
+    style
+};
@@ -1,3 +1,7 @@
 pub mod menu;
 pub mod hud;
+pub mod game_over;
+pub mod pause;
+pub mod replay;
+pub mod notice;
 mod button;
\ No newline at end of file
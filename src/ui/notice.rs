@@ -0,0 +1,58 @@
+use bevy::prelude::*;
+use crate::Notice;
+
+const NOTICE_SECONDS: f32 = 4.0;
+
+
+pub struct NoticePlugin;
+
+impl Plugin for NoticePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, notice_ui)
+            .add_systems(Update, show_notices);
+    }
+}
+
+#[derive(Component)]
+struct NoticeText;
+
+// the latest notice stays up for a few seconds whatever screen is showing
+fn show_notices(
+    mut notices: EventReader<Notice>,
+    time: Res<Time>,
+    mut notice: Query<(&mut Text, &mut Visibility), With<NoticeText>>,
+    mut remaining: Local<f32>,
+) {
+    let (mut text, mut visibility) = notice.single_mut();
+    if let Some(Notice(message)) = notices.iter().last() {
+        text.sections[0].value = message.clone();
+        *remaining = NOTICE_SECONDS;
+        *visibility = Visibility::Visible;
+    } else if *remaining > 0.0 {
+        *remaining -= time.delta_seconds();
+        if *remaining <= 0.0 {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
+
+fn notice_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle {
+            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+            font_size: 20.0,
+            color: Color::WHITE,
+        })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(2.0),
+                bottom: Val::Percent(2.0),
+                ..default()
+            })
+            .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.6)),
+        NoticeText,
+    )).insert(Visibility::Hidden);
+}
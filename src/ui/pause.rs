@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+use bevy::window::WindowFocused;
+use crate::GameState;
+use crate::net::lockstep::Lockstep;
+use crate::ui::button;
+
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, pause_ui)
+            // the other player in a networked game would be stuck waiting
+            .add_systems(Update, (
+                toggle_pause.run_if(in_state(GameState::Playing).or_else(in_state(GameState::Paused))),
+                pause_on_focus_loss.run_if(in_state(GameState::Playing)),
+            ).run_if(not(resource_exists::<Lockstep>())))
+            .add_systems(OnEnter(GameState::Paused), show_pause)
+            .add_systems(OnExit(GameState::Paused), hide_pause);
+    }
+}
+
+#[derive(Component)]
+struct PauseOverlay;
+
+fn toggle_pause(
+    input: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if input.any_just_pressed([KeyCode::Escape, KeyCode::P]) {
+        match state.get() {
+            GameState::Playing => next_state.set(GameState::Paused),
+            GameState::Paused => next_state.set(GameState::Playing),
+            _ => {}
+        }
+    }
+}
+
+fn pause_on_focus_loss(
+    mut events: EventReader<WindowFocused>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if events.iter().any(|event| !event.focused) {
+        next_state.set(GameState::Paused);
+    }
+}
+
+fn show_pause(mut overlay: Query<&mut Visibility, With<PauseOverlay>>) {
+    *overlay.single_mut() = Visibility::Visible;
+}
+
+fn hide_pause(mut overlay: Query<&mut Visibility, With<PauseOverlay>>) {
+    *overlay.single_mut() = Visibility::Hidden;
+}
+
+fn pause_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.spawn((NodeBundle {
+        background_color: BackgroundColor(Color::Hsla {
+            hue: 0.0,
+            saturation: 0.0,
+            lightness: 100.0,
+            alpha: 0.2,
+        }),
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            position_type: PositionType::Absolute,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        visibility: Visibility::Hidden,
+        ..default()
+    }, PauseOverlay))
+        .with_children(|parent| {
+            parent.spawn(NodeBundle {
+                background_color: BackgroundColor( Color::Hsla { hue: 0.0, saturation: 0.0, lightness: 100.0, alpha: 0.4 }),
+                style: Style {
+                    width: Val::Px(500.0),
+                    height: Val::Px(350.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::SpaceEvenly,
+                    align_items: AlignItems::Center,
+                    position_type: PositionType::Absolute,
+                    align_self: AlignSelf::Center,
+                    border: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                ..default()
+            }).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Paused",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 40.0,
+                        color: Color::rgb(0.1, 0.1, 0.1),
+                    },
+                ));
+                button::spawn_button(parent, &asset_server, "Resume");
+                button::spawn_button(parent, &asset_server, "Restart");
+                button::spawn_button(parent, &asset_server, "Quit");
+            });
+        });
+}
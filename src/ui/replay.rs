@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+use crate::GameState;
+use crate::replay::Playback;
+
+
+pub struct ReplayUiPlugin;
+
+impl Plugin for ReplayUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, replay_ui)
+            .add_systems(OnEnter(GameState::Replay), show_replay_ui)
+            .add_systems(OnExit(GameState::Replay), hide_replay_ui)
+            .add_systems(Update, update_replay_status.run_if(in_state(GameState::Replay)));
+    }
+}
+
+#[derive(Component)]
+struct ReplayOverlay;
+
+#[derive(Component)]
+struct ReplayStatus;
+
+fn show_replay_ui(mut overlay: Query<&mut Visibility, With<ReplayOverlay>>) {
+    *overlay.single_mut() = Visibility::Visible;
+}
+
+fn hide_replay_ui(mut overlay: Query<&mut Visibility, With<ReplayOverlay>>) {
+    *overlay.single_mut() = Visibility::Hidden;
+}
+
+fn update_replay_status(
+    playback: Option<Res<Playback>>,
+    mut status: Query<&mut Text, With<ReplayStatus>>,
+) {
+    let Some(playback) = playback else {
+        return;
+    };
+    let state = match (playback.finished(), playback.paused) {
+        (true, _) => "Finished",
+        (false, true) => "Paused",
+        (false, false) => "Playing",
+    };
+    status.single_mut().sections[0].value = format!(
+        "Replay  {} / {}  {}x  {}",
+        playback.tick, playback.replay.ticks(), playback.speed, state
+    );
+}
+
+fn replay_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    let text_style = |font_size: f32| TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(2.0),
+            top: Val::Percent(2.0),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(6.0),
+            ..default()
+        },
+        visibility: Visibility::Hidden,
+        ..default()
+    }, ReplayOverlay))
+        .with_children(|parent| {
+            parent.spawn((TextBundle::from_section("", text_style(24.0)), ReplayStatus));
+            parent.spawn(TextBundle::from_section(
+                "Space pause   N step   Up/Down speed\nLeft/Right seek   Home rewind   Esc menu",
+                text_style(16.0),
+            ));
+        });
+}